@@ -0,0 +1,145 @@
+//! Named locations saved from the viewer.
+//!
+//! The bookmark file is a text file with one section per bookmark :
+//!
+//! ```text
+//! [seahorse valley]
+//! plan = -0.15,-0.8,0.1,0.0666
+//! max_iter = 400
+//! fractal = mandelbrot
+//! power = 2
+//! ```
+//!
+//! `plan` and `max_iter` are required, the other lines are the options of
+//! the fractal, like `--fractal` or `--julia`.
+
+use std::io::{self, BufRead, Write};
+use plan::Plan;
+use raw::invalid_data;
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub plan: Plan,
+    pub max_iter: u32,
+    /// The fractal options, by name without the `--`.
+    pub options: Vec<(String, String)>,
+}
+
+/// Writes the bookmarks in the bookmark format.
+pub fn write_bookmarks<W: Write>(writer: &mut W, bookmarks: &[Bookmark]) -> io::Result<()> {
+    for bookmark in bookmarks {
+        let plan = &bookmark.plan;
+        writeln!(writer, "[{}]", bookmark.name)?;
+        writeln!(writer, "plan = {},{},{},{}", plan.up, plan.left, plan.width, plan.height)?;
+        writeln!(writer, "max_iter = {}", bookmark.max_iter)?;
+        for (name, value) in &bookmark.options {
+            writeln!(writer, "{} = {}", name, value)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Reads back a file written by `write_bookmarks`.
+pub fn read_bookmarks<R: BufRead>(reader: R) -> io::Result<Vec<Bookmark>> {
+    let mut section: Option<Section> = None;
+    let mut bookmarks = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            if let Some(section) = section.take() {
+                bookmarks.push(section.finish()?);
+            }
+            section = Some(Section {
+                name: line[1..line.len() - 1].trim().to_string(),
+                plan: None,
+                max_iter: None,
+                options: Vec::new(),
+            });
+            continue;
+        }
+        let section = match section {
+            Some(ref mut section) => section,
+            None                  => return Err(invalid_data("a bookmark file must start with `[name]`")),
+        };
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None               => return Err(invalid_data(&format!("invalid line `{}` in the bookmark `{}`", line, section.name))),
+        };
+        match key {
+            "plan"     => section.plan = Some(Plan::parse(value).map_err(|message| invalid_data(&format!("invalid plan in the bookmark `{}` : {}", section.name, message)))?),
+            "max_iter" => section.max_iter = Some(value.parse().map_err(|_| invalid_data(&format!("invalid max_iter in the bookmark `{}`", section.name)))?),
+            _          => section.options.push((key.to_string(), value.to_string())),
+        }
+    }
+    if let Some(section) = section {
+        bookmarks.push(section.finish()?);
+    }
+    Ok(bookmarks)
+}
+
+/// A bookmark being read, whose lines may come in any order.
+struct Section {
+    name: String,
+    plan: Option<Plan>,
+    max_iter: Option<u32>,
+    options: Vec<(String, String)>,
+}
+
+impl Section {
+    fn finish(self) -> io::Result<Bookmark> {
+        match (self.plan, self.max_iter) {
+            (Some(plan), Some(max_iter)) => Ok(Bookmark {name: self.name, plan, max_iter, options: self.options}),
+            _                            => Err(invalid_data(&format!("the bookmark `{}` needs a plan and a max_iter", self.name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let bookmarks = vec![
+            Bookmark {
+                name: "seahorse valley".to_string(),
+                plan: Plan {up: -0.15, left: -0.8, width: 0.1, height: 0.0625},
+                max_iter: 400,
+                options: Vec::new(),
+            },
+            Bookmark {
+                name: "cubic".to_string(),
+                plan: Plan {up: -1.5, left: -2., width: 3., height: 3.},
+                max_iter: 100,
+                options: vec![("power".to_string(), "3".to_string()), ("julia".to_string(), "-0.4,0.6".to_string())],
+            },
+        ];
+        let mut bytes = Vec::new();
+        write_bookmarks(&mut bytes, &bookmarks).unwrap();
+
+        let read = read_bookmarks(Cursor::new(bytes)).unwrap();
+        assert_eq!(read.len(), 2);
+        for (read, bookmark) in read.iter().zip(&bookmarks) {
+            assert_eq!(read.name, bookmark.name);
+            assert_eq!((read.plan.up, read.plan.left, read.plan.width, read.plan.height),
+                       (bookmark.plan.up, bookmark.plan.left, bookmark.plan.width, bookmark.plan.height));
+            assert_eq!(read.max_iter, bookmark.max_iter);
+            assert_eq!(read.options, bookmark.options);
+        }
+    }
+
+    #[test]
+    fn missing_plan_is_invalid() {
+        let text = "[seahorse valley]\nmax_iter = 400\nfractal = mandelbrot\n";
+        let err = read_bookmarks(Cursor::new(text)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("seahorse valley"));
+    }
+}
@@ -0,0 +1,168 @@
+//! The Buddhabrot : instead of coloring the points by their escape time, the
+//! orbits of the escaping points are traced and every pixel counts how many
+//! orbit points fell in it. The Nebulabrot does it for three iteration limits
+//! at once, one per color channel.
+
+use std::cmp;
+use std::sync::Arc;
+use std::thread;
+use complex::*;
+use plan::{Plan, ImageDim};
+use fractal::Fractal;
+use buffer::{DensityBuffer, FloatPixelBuffer};
+
+/// The `c` values are sampled in `[-EXTENT, EXTENT]^2`.
+const EXTENT: f64 = 2.;
+
+/// A xorshift64* generator, so the renders can be reproduced from a seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        //splitmix64 spreads close seeds apart, and the state can't be 0
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        Rng {
+            state: (z ^ (z >> 31)).max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64/(1u64 << 53) as f64
+    }
+}
+
+/// How the densities are turned into brightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// Proportional to the count, the default.
+    Linear,
+    Sqrt,
+    /// `ln(1 + count)`, which shows the faint orbits best.
+    Log,
+}
+
+impl ToneMap {
+    pub fn from_string(s: &str) -> Option<ToneMap> {
+        match s {
+            "linear" => Some(ToneMap::Linear),
+            "sqrt"   => Some(ToneMap::Sqrt),
+            "log"    => Some(ToneMap::Log),
+            _        => {
+                println!("Error : invalid tone mapping, it must be 'linear', 'sqrt' or 'log'.");
+                None
+            }
+        }
+    }
+
+    fn apply(self, count: f32) -> f32 {
+        match self {
+            ToneMap::Linear => count,
+            ToneMap::Sqrt   => count.sqrt(),
+            ToneMap::Log    => count.ln_1p(),
+        }
+    }
+}
+
+/// Traces the orbits of `samples` random points and counts their visits in
+/// the pixels of the Plan, one buffer per iteration limit : an orbit is
+/// counted in a buffer if it escapes within its limit. The samples are shared
+/// between the threads, each one having its own generator derived from the
+/// seed.
+pub fn compute_density(fractal: &Arc<dyn Fractal>, plan: &Plan, image_dim: &ImageDim, limits: &[u32], samples: u64, seed: u64, threads: usize) -> Vec<DensityBuffer> {
+    let threads = cmp::max(1, threads) as u64;
+    let workers: Vec<_> = (0..threads).map(|worker| {
+        let fractal = fractal.clone();
+        let plan = plan.clone();
+        let dim = image_dim.clone();
+        let limits = limits.to_vec();
+        let samples = samples/threads + if worker < samples % threads { 1 } else { 0 };
+        thread::spawn(move || {
+            let mut rng = Rng::new(seed.wrapping_add(worker));
+            let mut densities = vec![DensityBuffer::with_size(dim.width, dim.height); limits.len()];
+            let max_iter = limits.iter().cloned().max().unwrap_or(0);
+            let mut orbit = Vec::with_capacity(max_iter as usize);
+            for _ in 0..samples {
+                let pixel = c64::new((2.*rng.next_f64() - 1.)*EXTENT, (2.*rng.next_f64() - 1.)*EXTENT);
+                if fractal.inside(pixel) {
+                    continue;
+                }
+                let (mut z, c) = fractal.init(pixel);
+                orbit.clear();
+                while !fractal.escaped(z) && orbit.len() < max_iter as usize {
+                    z = fractal.step(z, c, pixel);
+                    orbit.push(z);
+                }
+                if !fractal.escaped(z) {
+                    continue;
+                }
+                for (density, &limit) in densities.iter_mut().zip(&limits) {
+                    if orbit.len() <= limit as usize {
+                        accumulate(density, &orbit, &plan);
+                    }
+                }
+            }
+            densities
+        })
+    }).collect();
+
+    let mut densities = vec![DensityBuffer::with_size(image_dim.width, image_dim.height); limits.len()];
+    for worker in workers {
+        for (density, worker_density) in densities.iter_mut().zip(worker.join().unwrap()) {
+            density.merge(&worker_density);
+        }
+    }
+    densities
+}
+
+fn accumulate(density: &mut DensityBuffer, orbit: &[c64], plan: &Plan) {
+    for z in orbit {
+        let x = (z.re() - plan.left)/plan.width*density.width as f64;
+        let y = (z.im() - plan.up)/plan.height*density.height as f64;
+        if x >= 0. && y >= 0. && x < density.width as f64 && y < density.height as f64 {
+            density.add(x as usize, y as usize);
+        }
+    }
+}
+
+/// Turns the densities into colors : one buffer gives a grayscale image,
+/// three give the red, green and blue channels. Each channel is scaled so that
+/// the `clip` percentile of its visited pixels is white, clipping the few
+/// brightest ones.
+pub fn tone_map(densities: &[DensityBuffer], tone: ToneMap, clip: f32) -> FloatPixelBuffer {
+    let (width, height) = (densities[0].width, densities[0].height);
+    let whites: Vec<f32> = densities.iter().map(|density| tone.apply(percentile(density, clip)).max(1e-6)).collect();
+    let mut pixel_buffer = FloatPixelBuffer::with_size(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let value = |channel: usize| {
+                let channel = channel % densities.len();
+                tone.apply(densities[channel].at(x, y) as f32)/whites[channel]
+            };
+            pixel_buffer.set(x, y, [value(0), value(1), value(2)]);
+        }
+    }
+    pixel_buffer
+}
+
+fn percentile(density: &DensityBuffer, clip: f32) -> f32 {
+    let mut counts: Vec<u32> = density.get().iter().cloned().filter(|&count| count > 0).collect();
+    if counts.is_empty() {
+        return 1.;
+    }
+    counts.sort_unstable();
+    let index = ((clip/100.).clamp(0., 1.)*(counts.len() - 1) as f32).round() as usize;
+    counts[index] as f32
+}
@@ -0,0 +1,196 @@
+/// An opaque color, with the same fields as the SFML one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    pub fn new_rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PixelArrayBuffer {
+    buffer: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PixelArrayBuffer {
+    pub fn with_size(width: usize, height: usize) -> PixelArrayBuffer {
+        PixelArrayBuffer {
+            buffer : vec![255; width*height*4],
+            width,
+            height,
+        }
+    }
+
+    /*fn at(&self, x: usize, y: usize) -> Color {
+        let color_index = 4*(x*self.height + y);
+        Color::new_rgb(
+            self.buffer[color_index],
+            self.buffer[color_index+1],
+            self.buffer[color_index+2]
+        )
+    }*/
+
+    pub fn get(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: &Color) {
+        let color_index = 4*(y*self.width + x);
+        self.buffer[color_index] = color.red;
+        self.buffer[color_index+1] = color.green;
+        self.buffer[color_index+2] = color.blue;
+    }
+}
+
+/// RGB pixels with float components, normally between 0 and 1, for the
+/// outputs with more than 8 bits per channel.
+#[derive(Debug)]
+pub struct FloatPixelBuffer {
+    buffer: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FloatPixelBuffer {
+    pub fn with_size(width: usize, height: usize) -> FloatPixelBuffer {
+        FloatPixelBuffer {
+            buffer : vec![1.; width*height*3],
+            width,
+            height,
+        }
+    }
+
+    pub fn get(&self) -> &[f32] {
+        &self.buffer
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: [f32; 3]) {
+        let color_index = 3*(y*self.width + x);
+        self.buffer[color_index..color_index+3].copy_from_slice(&color);
+    }
+
+    /// Rounds the colors to 8 bits, clamping them between 0 and 1.
+    pub fn to_pixel_array(&self) -> PixelArrayBuffer {
+        let mut pixel_buffer = PixelArrayBuffer::with_size(self.width, self.height);
+        for (index, color) in self.buffer.chunks(3).enumerate() {
+            let to_u8 = |value: f32| (value.clamp(0., 1.)*255.).round() as u8;
+            pixel_buffer.set(index % self.width, index/self.width, &Color::new_rgb(to_u8(color[0]), to_u8(color[1]), to_u8(color[2])));
+        }
+        pixel_buffer
+    }
+}
+
+/// Number of orbit points that fell in every pixel, for the Buddhabrot.
+#[derive(Debug, Clone)]
+pub struct DensityBuffer {
+    counts: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DensityBuffer {
+    pub fn with_size(width: usize, height: usize) -> DensityBuffer {
+        DensityBuffer {
+            counts: vec![0; width*height],
+            width,
+            height,
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> u32 {
+        self.counts[y*self.width + x]
+    }
+
+    pub fn get(&self) -> &[u32] {
+        &self.counts
+    }
+
+    pub fn add(&mut self, x: usize, y: usize) {
+        let count = &mut self.counts[y*self.width + x];
+        *count = count.saturating_add(1);
+    }
+
+    /// Adds the counts of another buffer of the same size.
+    pub fn merge(&mut self, other: &DensityBuffer) {
+        assert_eq!(self.counts.len(), other.counts.len());
+        for (count, &other) in self.counts.iter_mut().zip(&other.counts) {
+            *count = count.saturating_add(other);
+        }
+    }
+}
+
+/// Escape counts and smooth iteration counts of every pixel of a render,
+/// kept so the image can be colored again without running the escape loop.
+#[derive(Debug, Clone)]
+pub struct IterationBuffer {
+    iterations: Vec<u32>,
+    smooth: Vec<f32>,
+    //index + 1 of the root reached, 0 for none, or empty if the fractal has no roots
+    roots: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub max_iter: u32,
+}
+
+impl IterationBuffer {
+    pub fn from_vec(iterations: Vec<u32>, smooth: Vec<f32>, width: usize, height: usize, max_iter: u32) -> IterationBuffer {
+        assert_eq!(iterations.len(), width*height);
+        assert_eq!(smooth.len(), width*height);
+        IterationBuffer {
+            iterations,
+            smooth,
+            roots: Vec::new(),
+            width,
+            height,
+            max_iter,
+        }
+    }
+
+    /// Adds the roots reached by the pixels of a converging fractal, stored
+    /// as their index + 1, 0 meaning none.
+    pub fn with_roots(mut self, roots: Vec<u8>) -> IterationBuffer {
+        assert_eq!(roots.len(), self.width*self.height);
+        self.roots = roots;
+        self
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> u32 {
+        self.iterations[y*self.width + x]
+    }
+
+    /// The root reached by the pixel, if any.
+    pub fn root_at(&self, x: usize, y: usize) -> Option<u8> {
+        match self.roots.get(y*self.width + x) {
+            Some(&root) if root > 0 => Some(root - 1),
+            _                       => None,
+        }
+    }
+
+    pub fn smooth_at(&self, x: usize, y: usize) -> f32 {
+        self.smooth[y*self.width + x]
+    }
+
+    pub fn get(&self) -> &[u32] {
+        &self.iterations
+    }
+
+    pub fn get_smooth(&self) -> &[f32] {
+        &self.smooth
+    }
+
+    /// The encoded roots, empty if the fractal has none.
+    pub fn get_roots(&self) -> &[u8] {
+        &self.roots
+    }
+}
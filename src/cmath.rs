@@ -0,0 +1,55 @@
+//! Complex functions that the `complex` crate doesn't provide.
+
+use complex::*;
+
+pub fn norm_sqr(z: c64) -> f64 {
+    z.re()*z.re() + z.im()*z.im()
+}
+
+pub fn exp(z: c64) -> c64 {
+    c64::from_polar(z.re().exp(), z.im())
+}
+
+/// Principal logarithm.
+pub fn ln(z: c64) -> c64 {
+    c64::new(z.abs().ln(), z.arg())
+}
+
+/// `z^n` by repeated squaring.
+pub fn powi(z: c64, n: u32) -> c64 {
+    let mut result = c64::new(1., 0.);
+    let mut base = z;
+    let mut n = n;
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result*base;
+        }
+        base = base*base;
+        n >>= 1;
+    }
+    result
+}
+
+/// `z^d` for a real `d`, in polar form.
+pub fn powf(z: c64, d: f64) -> c64 {
+    if z.re() == 0. && z.im() == 0. {
+        return z;
+    }
+    c64::from_polar(z.abs().powf(d), z.arg()*d)
+}
+
+/// `z^w = exp(w*ln(z))`, with `0^w = 0`.
+pub fn powc(z: c64, w: c64) -> c64 {
+    if z.re() == 0. && z.im() == 0. {
+        return z;
+    }
+    exp(w*ln(z))
+}
+
+pub fn sin(z: c64) -> c64 {
+    c64::new(z.re().sin()*z.im().cosh(), z.re().cos()*z.im().sinh())
+}
+
+pub fn cos(z: c64) -> c64 {
+    c64::new(z.re().cos()*z.im().cosh(), -z.re().sin()*z.im().sinh())
+}
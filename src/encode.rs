@@ -0,0 +1,340 @@
+//! Built-in image encoders, so images can be written without SFML.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use buffer::{PixelArrayBuffer, FloatPixelBuffer};
+use zlib;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// Binary RGB portable pixmap (P6).
+    Ppm,
+    /// Binary grayscale portable graymap (P5).
+    Pgm,
+    /// Uncompressed 24 bits BMP.
+    Bmp,
+    /// Radiance RGBE, only for float pixels.
+    Hdr,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &str) -> Option<ImageFormat> {
+        let extension = match path.rfind('.') {
+            Some(dot) => path[dot + 1..].to_lowercase(),
+            None      => return None,
+        };
+        match &(*extension) {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pgm" => Some(ImageFormat::Pgm),
+            "bmp" => Some(ImageFormat::Bmp),
+            "hdr" => Some(ImageFormat::Hdr),
+            _     => None,
+        }
+    }
+}
+
+/// Encodes the pixels in the given format.
+pub fn write_image<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer, format: ImageFormat) -> io::Result<()> {
+    match format {
+        ImageFormat::Png => write_png(writer, pixel_buffer),
+        ImageFormat::Ppm => write_ppm(writer, pixel_buffer),
+        ImageFormat::Pgm => write_pgm(writer, pixel_buffer),
+        ImageFormat::Bmp => write_bmp(writer, pixel_buffer),
+        ImageFormat::Hdr => Err(io::Error::new(io::ErrorKind::InvalidInput, "hdr images need float pixels")),
+    }
+}
+
+/// Encodes float pixels, as a 16 bits PNG or a Radiance HDR image.
+pub fn write_float_image<W: Write>(writer: &mut W, pixel_buffer: &FloatPixelBuffer, format: ImageFormat) -> io::Result<()> {
+    match format {
+        ImageFormat::Png => write_png16(writer, pixel_buffer),
+        ImageFormat::Hdr => write_hdr(writer, pixel_buffer),
+        _                => Err(io::Error::new(io::ErrorKind::InvalidInput, "only png and hdr support more than 8 bits per channel")),
+    }
+}
+
+/// Writes the pixels to a file, in the format given by its extension.
+pub fn save_image(pixel_buffer: &PixelArrayBuffer, path: &str) -> io::Result<()> {
+    let format = format_of(path)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_image(&mut writer, pixel_buffer, format)?;
+    writer.flush()
+}
+
+/// Writes float pixels to a png or hdr file.
+pub fn save_float_image(pixel_buffer: &FloatPixelBuffer, path: &str) -> io::Result<()> {
+    let format = format_of(path)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_float_image(&mut writer, pixel_buffer, format)?;
+    writer.flush()
+}
+
+fn format_of(path: &str) -> io::Result<ImageFormat> {
+    ImageFormat::from_path(path).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unsupported image format"))
+}
+
+fn rgb(pixel_buffer: &PixelArrayBuffer) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(pixel_buffer.width*pixel_buffer.height*3);
+    for pixel in pixel_buffer.get().chunks(4) {
+        rgb.extend(&pixel[..3]);
+    }
+    rgb
+}
+
+fn luma(pixel: &[u8]) -> u8 {
+    (0.299*pixel[0] as f32 + 0.587*pixel[1] as f32 + 0.114*pixel[2] as f32).round() as u8
+}
+
+pub fn write_ppm<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", pixel_buffer.width, pixel_buffer.height)?;
+    writer.write_all(&rgb(pixel_buffer))
+}
+
+pub fn write_pgm<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer) -> io::Result<()> {
+    write!(writer, "P5\n{} {}\n255\n", pixel_buffer.width, pixel_buffer.height)?;
+    let gray: Vec<u8> = pixel_buffer.get().chunks(4).map(luma).collect();
+    writer.write_all(&gray)
+}
+
+pub fn write_bmp<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer) -> io::Result<()> {
+    //rows are stored bottom-up, in BGR, padded to 4 bytes
+    let row_size = (pixel_buffer.width*3).div_ceil(4)*4;
+    let data_size = row_size*pixel_buffer.height;
+    writer.write_all(b"BM")?;
+    writer.write_all(&(54 + data_size as u32).to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&54u32.to_le_bytes())?;
+    //BITMAPINFOHEADER
+    writer.write_all(&40u32.to_le_bytes())?;
+    writer.write_all(&(pixel_buffer.width as i32).to_le_bytes())?;
+    writer.write_all(&(pixel_buffer.height as i32).to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&24u16.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(data_size as u32).to_le_bytes())?;
+    writer.write_all(&2835u32.to_le_bytes())?; //72 dpi
+    writer.write_all(&2835u32.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+
+    let mut row = vec![0; row_size];
+    for y in (0..pixel_buffer.height).rev() {
+        let line = &pixel_buffer.get()[4*y*pixel_buffer.width..4*(y + 1)*pixel_buffer.width];
+        for (x, pixel) in line.chunks(4).enumerate() {
+            row[3*x] = pixel[2];
+            row[3*x + 1] = pixel[1];
+            row[3*x + 2] = pixel[0];
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+pub fn write_png<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer) -> io::Result<()> {
+    write_png_data(writer, pixel_buffer.width, pixel_buffer.height, 8, &rgb(pixel_buffer))
+}
+
+/// Writes a PNG with `tEXt` chunks, given as `(keyword, text)` pairs, like
+/// the parameters of the render.
+pub fn write_png_text<W: Write>(writer: &mut W, pixel_buffer: &PixelArrayBuffer, texts: &[(&str, &str)]) -> io::Result<()> {
+    write_png_chunks(writer, pixel_buffer.width, pixel_buffer.height, 8, &rgb(pixel_buffer), texts)
+}
+
+/// Writes a PNG with 16 bits per channel.
+pub fn write_png16<W: Write>(writer: &mut W, pixel_buffer: &FloatPixelBuffer) -> io::Result<()> {
+    let mut samples = Vec::with_capacity(pixel_buffer.get().len()*2);
+    for &value in pixel_buffer.get() {
+        let value = (value.clamp(0., 1.)*65535.).round() as u16;
+        samples.extend(&value.to_be_bytes());
+    }
+    write_png_data(writer, pixel_buffer.width, pixel_buffer.height, 16, &samples)
+}
+
+/// Writes a Radiance HDR image, with uncompressed scanlines.
+pub fn write_hdr<W: Write>(writer: &mut W, pixel_buffer: &FloatPixelBuffer) -> io::Result<()> {
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", pixel_buffer.height, pixel_buffer.width)?;
+    let mut rgbe = Vec::with_capacity(pixel_buffer.width*pixel_buffer.height*4);
+    for pixel in pixel_buffer.get().chunks(3) {
+        let brightest = pixel[0].max(pixel[1]).max(pixel[2]);
+        if brightest < 1e-32 {
+            rgbe.extend(&[0, 0, 0, 0]);
+        } else {
+            //shared exponent such that brightest = mantissa*2^exponent, with mantissa in [0.5, 1)
+            let exponent = brightest.log2().floor() as i32 + 1;
+            let scale = 256./2f32.powi(exponent);
+            rgbe.extend(&[
+                (pixel[0]*scale).min(255.) as u8,
+                (pixel[1]*scale).min(255.) as u8,
+                (pixel[2]*scale).min(255.) as u8,
+                (exponent + 128) as u8,
+            ]);
+        }
+    }
+    writer.write_all(&rgbe)
+}
+
+/// Writes an RGB PNG from the packed samples of the image, `bit_depth` being
+/// 8 or 16 (16 bits samples are big-endian).
+pub fn write_png_data<W: Write>(writer: &mut W, width: usize, height: usize, bit_depth: u8, samples: &[u8]) -> io::Result<()> {
+    write_png_chunks(writer, width, height, bit_depth, samples, &[])
+}
+
+fn write_png_chunks<W: Write>(writer: &mut W, width: usize, height: usize, bit_depth: u8, samples: &[u8], texts: &[(&str, &str)]) -> io::Result<()> {
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "a png image can't have a zero width or height"));
+    }
+    let row_size = width*3*bit_depth as usize/8;
+    //every row starts with its filter type, 0 (none)
+    let mut filtered = Vec::with_capacity((row_size + 1)*height);
+    for row in samples.chunks(row_size).take(height) {
+        filtered.push(0);
+        filtered.extend(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    header.extend(&[bit_depth, 2, 0, 0, 0]); //truecolor, deflate, no filtering method, no interlace
+
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(writer, b"IHDR", &header)?;
+    write_chunk(writer, b"IDAT", &zlib::compress(&filtered))?;
+    for (keyword, text) in texts {
+        //the keyword and the text are separated by a null byte
+        let mut data = Vec::with_capacity(keyword.len() + 1 + text.len());
+        data.extend(keyword.as_bytes());
+        data.push(0);
+        data.extend(text.as_bytes());
+        write_chunk(writer, b"tEXt", &data)?;
+    }
+    write_chunk(writer, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(&[&kind[..], data]);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    let mut crc = 0xffffffffu32;
+    for part in parts {
+        for &byte in part.iter() {
+            crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buffer::Color;
+
+    fn sample() -> PixelArrayBuffer {
+        let mut pixel_buffer = PixelArrayBuffer::with_size(3, 2);
+        pixel_buffer.set(0, 0, &Color::new_rgb(255, 0, 0));
+        pixel_buffer.set(1, 0, &Color::new_rgb(0, 255, 0));
+        pixel_buffer.set(2, 0, &Color::new_rgb(0, 0, 255));
+        pixel_buffer.set(0, 1, &Color::new_rgb(10, 20, 30));
+        pixel_buffer
+    }
+
+    /// The `(kind, data)` chunks of a PNG, checking their CRC.
+    fn chunks(png: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes([rest[8 + length], rest[9 + length], rest[10 + length], rest[11 + length]]);
+            assert_eq!(crc, crc32(&[kind, data]));
+            chunks.push((kind.to_vec(), data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(&[b"IEND"]), 0xae426082);
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xcbf43926);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let mut png = Vec::new();
+        write_png_text(&mut png, &sample(), &[("Software", "mandelbrot")]).unwrap();
+        let chunks = chunks(&png);
+        let kinds: Vec<&[u8]> = chunks.iter().map(|chunk| &chunk.0[..]).collect();
+        assert_eq!(kinds, vec![&b"IHDR"[..], b"IDAT", b"tEXt", b"IEND"]);
+        assert_eq!(chunks[0].1, vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        assert_eq!(zlib::decompress(&chunks[1].1), vec![
+            0, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+            0, 10, 20, 30, 255, 255, 255, 255, 255, 255,
+        ]);
+        assert_eq!(chunks[2].1, b"Software\0mandelbrot".to_vec());
+    }
+
+    #[test]
+    fn png16_samples_are_big_endian() {
+        let mut pixel_buffer = FloatPixelBuffer::with_size(1, 1);
+        pixel_buffer.set(0, 0, [1., 0.5, 0.]);
+        let mut png = Vec::new();
+        write_png16(&mut png, &pixel_buffer).unwrap();
+        let chunks = chunks(&png);
+        assert_eq!(chunks[0].1[8], 16);
+        assert_eq!(zlib::decompress(&chunks[1].1), vec![0, 255, 255, 128, 0, 0, 0]);
+    }
+
+    #[test]
+    fn empty_png_is_invalid() {
+        for &(width, height) in &[(0, 60), (100, 0)] {
+            let err = write_png(&mut Vec::new(), &PixelArrayBuffer::with_size(width, height)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn ppm_and_pgm() {
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, &sample()).unwrap();
+        let mut expected = b"P6\n3 2\n255\n".to_vec();
+        expected.extend(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 10, 20, 30, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(ppm, expected);
+
+        let mut pgm = Vec::new();
+        write_pgm(&mut pgm, &sample()).unwrap();
+        let mut expected = b"P5\n3 2\n255\n".to_vec();
+        expected.extend(&[76, 150, 29, 18, 255, 255]);
+        assert_eq!(pgm, expected);
+    }
+
+    #[test]
+    fn bmp_rows_are_bottom_up_and_padded() {
+        let mut bmp = Vec::new();
+        write_bmp(&mut bmp, &sample()).unwrap();
+        //3 pixels of 3 bytes, padded to 12 bytes per row
+        assert_eq!(bmp.len(), 54 + 2*12);
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(u32::from_le_bytes([bmp[2], bmp[3], bmp[4], bmp[5]]), 78);
+        assert_eq!(i32::from_le_bytes([bmp[18], bmp[19], bmp[20], bmp[21]]), 3);
+        assert_eq!(i32::from_le_bytes([bmp[22], bmp[23], bmp[24], bmp[25]]), 2);
+        assert_eq!(&bmp[54..66], &[30, 20, 10, 255, 255, 255, 255, 255, 255, 0, 0, 0]);
+        assert_eq!(&bmp[66..78], &[0, 0, 255, 0, 255, 0, 255, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,541 @@
+//! Formulas typed on the command line, like `z^3 - z + c` or `sin(z)*c`.
+//!
+//! An expression uses the variables `z`, `c` and `pixel`, the constants `i`,
+//! `pi` and `e`, real numbers, the operators `+ - * / ^` and the functions
+//! `exp`, `log`, `sin`, `cos`, `abs` (the modulus) and `conj`. It is parsed
+//! once, its constant parts are folded, and it is compiled to a small stack
+//! program evaluated at every iteration.
+//!
+//! `z` starts at `c`, unless the formula begins with a starting value and a
+//! semicolon, as in `0; z^2 + c`.
+
+use std::cmp;
+use complex::*;
+use fractal::Fractal;
+use cmath;
+
+/// Deepest stack a compiled expression may need.
+const MAX_STACK: usize = 32;
+/// Deepest nesting of parentheses, signs and powers the parser follows,
+/// well below what would overflow its own stack.
+const MAX_NESTING: usize = 64;
+/// Longest formula, in tokens. A chain like `z + z + ... + z` nests its
+/// operations as deep as it is long, and the passes over the tree recurse
+/// that deep.
+const MAX_TOKENS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Neg,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Abs,
+    Conj,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "exp"  => Some(Function::Exp),
+            "log"  => Some(Function::Log),
+            "sin"  => Some(Function::Sin),
+            "cos"  => Some(Function::Cos),
+            "abs"  => Some(Function::Abs),
+            "conj" => Some(Function::Conj),
+            _      => None,
+        }
+    }
+
+    fn apply(self, z: c64) -> c64 {
+        match self {
+            Function::Neg  => -z,
+            Function::Exp  => cmath::exp(z),
+            Function::Log  => cmath::ln(z),
+            Function::Sin  => cmath::sin(z),
+            Function::Cos  => cmath::cos(z),
+            Function::Abs  => c64::new(z.abs(), 0.),
+            Function::Conj => z.conj(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl Operator {
+    fn apply(self, a: c64, b: c64) -> c64 {
+        match self {
+            Operator::Add => a + b,
+            Operator::Sub => a - b,
+            Operator::Mul => a*b,
+            Operator::Div => a/b,
+            Operator::Pow => cmath::powc(a, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Z,
+    C,
+    Pixel,
+    Const(c64),
+    Call(Function, Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    /// Replaces the subtrees without variables by their value.
+    fn fold(self) -> Node {
+        match self {
+            Node::Call(function, argument) => match argument.fold() {
+                Node::Const(a) => Node::Const(function.apply(a)),
+                argument       => Node::Call(function, Box::new(argument)),
+            },
+            Node::Binary(operator, left, right) => match (left.fold(), right.fold()) {
+                (Node::Const(a), Node::Const(b)) => Node::Const(operator.apply(a, b)),
+                (left, right)                    => Node::Binary(operator, Box::new(left), Box::new(right)),
+            },
+            node => node,
+        }
+    }
+
+    /// Number of stack slots needed to evaluate the node.
+    fn depth(&self) -> usize {
+        match *self {
+            Node::Call(_, ref argument)          => argument.depth(),
+            Node::Binary(_, ref left, ref right) => cmp::max(left.depth(), right.depth() + 1),
+            _                                    => 1,
+        }
+    }
+
+    fn emit(&self, code: &mut Vec<Op>) {
+        match *self {
+            Node::Z            => code.push(Op::Z),
+            Node::C            => code.push(Op::C),
+            Node::Pixel        => code.push(Op::Pixel),
+            Node::Const(value) => code.push(Op::Const(value)),
+            Node::Call(function, ref argument) => {
+                argument.emit(code);
+                code.push(Op::Call(function));
+            },
+            Node::Binary(operator, ref left, ref right) => {
+                left.emit(code);
+                match (operator, &**right) {
+                    //small integer powers are cheaper as multiplications
+                    (Operator::Pow, &Node::Const(n)) if n.im() == 0. && n.re() >= 1. && n.re() <= 64. && n.re().fract() == 0. => {
+                        code.push(Op::PowInt(n.re() as u32));
+                    },
+                    _ => {
+                        right.emit(code);
+                        code.push(Op::Binary(operator));
+                    },
+                }
+            },
+        }
+    }
+}
+
+/// An instruction of the compiled program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Z,
+    C,
+    Pixel,
+    Const(c64),
+    Call(Function),
+    Binary(Operator),
+    PowInt(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(usize, usize),
+    Symbol(char),
+    End,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    nesting: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Parser<'a>, String> {
+        let chars: Vec<(usize, char)> = source.char_indices().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let (start, ch) = chars[i];
+            if ch.is_whitespace() {
+                i += 1;
+            } else if ch.is_ascii_digit() || ch == '.' {
+                while i < chars.len() && (chars[i].1.is_ascii_digit() || chars[i].1 == '.') {
+                    i += 1;
+                }
+                let end = if i < chars.len() { chars[i].0 } else { source.len() };
+                match source[start..end].parse() {
+                    Ok(value) => tokens.push((start, Token::Number(value))),
+                    Err(_)    => return Err(format!("invalid number `{}`", &source[start..end])),
+                }
+            } else if ch.is_ascii_alphabetic() {
+                while i < chars.len() && chars[i].1.is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let end = if i < chars.len() { chars[i].0 } else { source.len() };
+                tokens.push((start, Token::Name(start, end)));
+            } else if "+-*/^()".contains(ch) {
+                tokens.push((start, Token::Symbol(ch)));
+                i += 1;
+            } else {
+                return Err(format!("unexpected `{}` at position {}", ch, start + 1));
+            }
+            if tokens.len() > MAX_TOKENS {
+                return Err(format!("the expression is longer than {} tokens", MAX_TOKENS));
+            }
+        }
+        tokens.push((source.len(), Token::End));
+        Ok(Parser {
+            source,
+            tokens,
+            position: 0,
+            nesting: 0,
+        })
+    }
+
+    fn peek(&self) -> Token {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, expected: &str) -> String {
+        let (at, token) = self.tokens[self.position];
+        match token {
+            Token::End => format!("expected {} at the end", expected),
+            _          => format!("expected {} at position {}", expected, at + 1),
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.peek() == Token::Symbol(symbol) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", symbol)))
+        }
+    }
+
+    //expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Node, String> {
+        let mut node = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol('+') => Operator::Add,
+                Token::Symbol('-') => Operator::Sub,
+                _                  => return Ok(node),
+            };
+            self.next();
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    //term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol('*') => Operator::Mul,
+                Token::Symbol('/') => Operator::Div,
+                _                  => return Ok(node),
+            };
+            self.next();
+            node = Node::Binary(operator, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    //unary := '-' unary | power, every nested subexpression going through it
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.nesting == MAX_NESTING {
+            return Err(format!("the expression is nested more than {} levels deep", MAX_NESTING));
+        }
+        self.nesting += 1;
+        let node = if self.peek() == Token::Symbol('-') {
+            self.next();
+            self.unary().map(|node| Node::Call(Function::Neg, Box::new(node)))
+        } else {
+            self.power()
+        };
+        self.nesting -= 1;
+        node
+    }
+
+    //power := atom ('^' unary)?, so that -z^2 is -(z^2) and z^-1 works
+    fn power(&mut self) -> Result<Node, String> {
+        let node = self.atom()?;
+        if self.peek() == Token::Symbol('^') {
+            self.next();
+            Ok(Node::Binary(Operator::Pow, Box::new(node), Box::new(self.unary()?)))
+        } else {
+            Ok(node)
+        }
+    }
+
+    //atom := number | variable | constant | function '(' expression ')' | '(' expression ')'
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Token::Number(value) => {
+                self.next();
+                Ok(Node::Const(c64::new(value, 0.)))
+            },
+            Token::Symbol('(') => {
+                self.next();
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            },
+            Token::Name(start, end) => {
+                self.next();
+                let name = &self.source[start..end];
+                match name {
+                    "z"     => Ok(Node::Z),
+                    "c"     => Ok(Node::C),
+                    "pixel" => Ok(Node::Pixel),
+                    "i"     => Ok(Node::Const(c64::new(0., 1.))),
+                    "pi"    => Ok(Node::Const(c64::new(::std::f64::consts::PI, 0.))),
+                    "e"     => Ok(Node::Const(c64::new(::std::f64::consts::E, 0.))),
+                    _       => match Function::from_name(name) {
+                        Some(function) => {
+                            self.expect('(')?;
+                            let argument = self.expression()?;
+                            self.expect(')')?;
+                            Ok(Node::Call(function, Box::new(argument)))
+                        },
+                        None => Err(format!("unknown name `{}` at position {}", name, start + 1)),
+                    },
+                }
+            },
+            _ => Err(self.error("a number, a name or `(`")),
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Node, String> {
+    let mut parser = Parser::new(source)?;
+    let node = parser.expression()?;
+    if parser.peek() != Token::End {
+        return Err(parser.error("an operator"));
+    }
+    Ok(node.fold())
+}
+
+/// A compiled expression.
+#[derive(Debug, Clone)]
+struct Program {
+    code: Vec<Op>,
+}
+
+impl Program {
+    fn compile(source: &str) -> Result<Program, String> {
+        let node = parse(source)?;
+        if node.depth() > MAX_STACK {
+            return Err("the expression is too deeply nested".to_string());
+        }
+        let mut code = Vec::new();
+        node.emit(&mut code);
+        Ok(Program {
+            code,
+        })
+    }
+
+    fn eval(&self, z: c64, c: c64, pixel: c64) -> c64 {
+        let mut stack = [c64::new(0., 0.); MAX_STACK];
+        let mut top = 0;
+        for op in &self.code {
+            match *op {
+                Op::Z               => { stack[top] = z; top += 1; },
+                Op::C               => { stack[top] = c; top += 1; },
+                Op::Pixel           => { stack[top] = pixel; top += 1; },
+                Op::Const(value)    => { stack[top] = value; top += 1; },
+                Op::Call(function)  => stack[top - 1] = function.apply(stack[top - 1]),
+                Op::PowInt(n)       => stack[top - 1] = cmath::powi(stack[top - 1], n),
+                Op::Binary(operator) => {
+                    top -= 1;
+                    stack[top - 1] = operator.apply(stack[top - 1], stack[top]);
+                },
+            }
+        }
+        stack[0]
+    }
+}
+
+/// Reads a constant expression, like `2.5` or `1 - 2*i`.
+pub fn constant(s: &str) -> Option<c64> {
+    match parse(s) {
+        Ok(Node::Const(value)) => Some(value),
+        Ok(_)                  => {
+            println!("Error : `{}` isn't a constant.", s.trim());
+            None
+        },
+        Err(message)           => {
+            println!("Error : invalid constant `{}` : {}.", s.trim(), message);
+            None
+        }
+    }
+}
+
+fn compile(s: &str) -> Result<(Option<Program>, Program), String> {
+    match s.find(';') {
+        Some(semicolon) => Ok((Some(Program::compile(&s[..semicolon])?), Program::compile(&s[semicolon + 1..])?)),
+        None            => Ok((None, Program::compile(s)?)),
+    }
+}
+
+/// A fractal iterating a formula given at runtime.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    start: Option<Program>,
+    step: Program,
+    degree: f64,
+}
+
+impl Expression {
+    /// Compiles `"step"` or `"start; step"`.
+    pub fn from_string(s: &str) -> Option<Expression> {
+        match compile(s) {
+            Ok((start, step)) => {
+                let mut expression = Expression {
+                    source: s.trim().to_string(),
+                    start,
+                    step,
+                    degree: 2.,
+                };
+                expression.degree = expression.estimate_degree();
+                Some(expression)
+            },
+            Err(message) => {
+                println!("Error : invalid formula : {}.", message);
+                None
+            }
+        }
+    }
+
+    /// Growth rate of `|z|` far from 0, for the smooth coloring : `d` for a
+    /// polynomial of degree `d`, 2 when `|z|` doesn't grow like a power.
+    fn estimate_degree(&self) -> f64 {
+        let z = c64::from_polar(1e3, 0.3);
+        let c = c64::new(0.5, 0.5);
+        let degree = self.step.eval(z, c, c).abs().ln()/z.abs().ln();
+        if degree.is_finite() && degree > 1.1 { degree } else { 2. }
+    }
+}
+
+impl Fractal for Expression {
+    fn name(&self) -> &str {
+        &self.source
+    }
+
+    fn init(&self, pixel: c64) -> (c64, c64) {
+        match self.start {
+            Some(ref start) => (start.eval(c64::new(0., 0.), pixel, pixel), pixel),
+            None            => (pixel, pixel),
+        }
+    }
+
+    fn step(&self, z: c64, c: c64, pixel: c64) -> c64 {
+        self.step.eval(z, c, pixel)
+    }
+
+    fn degree(&self) -> f64 {
+        self.degree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(operator: Operator, left: Node, right: Node) -> Node {
+        Node::Binary(operator, Box::new(left), Box::new(right))
+    }
+
+    fn real(value: f64) -> Node {
+        Node::Const(c64::new(value, 0.))
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("z + c*2").unwrap(), binary(Operator::Add, Node::Z, binary(Operator::Mul, Node::C, real(2.))));
+        assert_eq!(parse("(z + c)*2").unwrap(), binary(Operator::Mul, binary(Operator::Add, Node::Z, Node::C), real(2.)));
+        assert_eq!(parse("-z^2").unwrap(), Node::Call(Function::Neg, Box::new(binary(Operator::Pow, Node::Z, real(2.)))));
+        assert_eq!(parse("z - pixel - c").unwrap(), binary(Operator::Sub, binary(Operator::Sub, Node::Z, Node::Pixel), Node::C));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("z +").unwrap_err(), "expected a number, a name or `(` at the end");
+        assert_eq!(parse("z c").unwrap_err(), "expected an operator at position 3");
+        assert_eq!(parse("foo(z)").unwrap_err(), "unknown name `foo` at position 1");
+        assert_eq!(parse("sin z").unwrap_err(), "expected `(` at position 5");
+        assert_eq!(parse("(z").unwrap_err(), "expected `)` at the end");
+        assert_eq!(parse("z # c").unwrap_err(), "unexpected `#` at position 3");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let deep = format!("{}z{}", "(".repeat(MAX_NESTING + 1), ")".repeat(MAX_NESTING + 1));
+        assert!(parse(&deep).unwrap_err().contains("nested"));
+        assert!(parse(&"-".repeat(MAX_NESTING + 1)).unwrap_err().contains("nested"));
+        assert!(parse(&"(".repeat(100000)).unwrap_err().contains("longer"));
+        assert!(parse(&"z+".repeat(100000)).unwrap_err().contains("longer"));
+        assert!(parse(&format!("{}z", "z*".repeat(100000))).unwrap_err().contains("longer"));
+        let long = format!("{}z", "z+".repeat(MAX_TOKENS/2 - 1));
+        assert_eq!(parse(&long).unwrap().depth(), 2);
+        let shallow = format!("{}z{}", "(".repeat(MAX_NESTING - 1), ")".repeat(MAX_NESTING - 1));
+        assert_eq!(parse(&shallow).unwrap(), Node::Z);
+    }
+
+    #[test]
+    fn constant_folding() {
+        assert_eq!(parse("2*3 + i").unwrap(), Node::Const(c64::new(6., 1.)));
+        assert_eq!(parse("z + 2*pi").unwrap(), binary(Operator::Add, Node::Z, real(2.*::std::f64::consts::PI)));
+        assert_eq!(parse("z*-(1 - 3)").unwrap(), binary(Operator::Mul, Node::Z, real(2.)));
+        assert_eq!(constant("1 - 2*i"), Some(c64::new(1., -2.)));
+        assert_eq!(constant("z"), None);
+    }
+
+    #[test]
+    fn integer_powers() {
+        assert_eq!(Program::compile("z^3 + c").unwrap().code, vec![Op::Z, Op::PowInt(3), Op::C, Op::Binary(Operator::Add)]);
+        assert_eq!(Program::compile("z^(1 + 1)").unwrap().code, vec![Op::Z, Op::PowInt(2)]);
+        assert_eq!(Program::compile("z^2.5").unwrap().code, vec![Op::Z, Op::Const(c64::new(2.5, 0.)), Op::Binary(Operator::Pow)]);
+        assert_eq!(Program::compile("z^0").unwrap().code, vec![Op::Z, Op::Const(c64::new(0., 0.)), Op::Binary(Operator::Pow)]);
+        assert_eq!(Program::compile("z^c").unwrap().code, vec![Op::Z, Op::C, Op::Binary(Operator::Pow)]);
+    }
+
+    #[test]
+    fn eval() {
+        let program = Program::compile("z^2 + c").unwrap();
+        assert_eq!(program.eval(c64::new(1., 1.), c64::new(1., 0.), c64::new(0., 0.)), c64::new(1., 2.));
+        let program = Program::compile("z*pixel - c/2").unwrap();
+        assert_eq!(program.eval(c64::new(2., 0.), c64::new(1., 0.), c64::new(0., 1.)), c64::new(-0.5, 2.));
+    }
+}
@@ -0,0 +1,482 @@
+//! The formulas the escape-time engine can iterate.
+//!
+//! A formula is anything implementing `Fractal`. The `Registry` maps names to
+//! formulas, so the command line can pick them with `--fractal NAME`, and a
+//! program using this library can register its own :
+//!
+//! ```
+//! extern crate complex;
+//! extern crate mandelbrot;
+//!
+//! use complex::*;
+//! use mandelbrot::fractal::{Fractal, Registry};
+//!
+//! //z = z^2 + c + 1/c
+//! struct Perturbed;
+//!
+//! impl Fractal for Perturbed {
+//!     fn name(&self) -> &str { "perturbed" }
+//!     fn init(&self, pixel: c64) -> (c64, c64) { (c64::new(0., 0.), pixel) }
+//!     fn step(&self, z: c64, c: c64, _pixel: c64) -> c64 { z*z + c + c.conj()/(c.re()*c.re() + c.im()*c.im()) }
+//! }
+//!
+//! fn main() {
+//!     let mut registry = Registry::with_builtins();
+//!     registry.register("perturbed", |_| Box::new(Perturbed));
+//!     assert!(registry.create("perturbed", &Default::default()).is_some());
+//! }
+//! ```
+
+use std::sync::Arc;
+use complex::*;
+use plan::{Plan, complex_from_string};
+use render::escape_time;
+use newton::{Polynomial, Newton, Nova};
+use cmath;
+
+/// A function iterated for every pixel until `z` escapes.
+pub trait Fractal: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The starting `z` and the constant `c` for a pixel.
+    fn init(&self, pixel: c64) -> (c64, c64);
+
+    /// One iteration. `pixel` is the point of the image, which is `c` for
+    /// a Mandelbrot-like set and the starting `z` for a Julia set.
+    fn step(&self, z: c64, c: c64, pixel: c64) -> c64;
+
+    /// Whether the pixel is known to be in the set without iterating, which
+    /// saves running to max_iter. `false` by default.
+    fn inside(&self, _pixel: c64) -> bool {
+        false
+    }
+
+    /// Whether the orbit escaped, `|z| >= 2` by default.
+    fn escaped(&self, z: c64) -> bool {
+        cmath::norm_sqr(z) >= 4.
+    }
+
+    /// The frame used when the plan is `?`.
+    fn default_plan(&self) -> Plan {
+        fit_plan(self)
+    }
+
+    /// The frame used for the Julia sets when the plan is `?`, or None to
+    /// fit it around each set.
+    fn julia_plan(&self) -> Option<Plan> {
+        None
+    }
+
+    /// Growth rate of `|z|` once it escaped, used by the smooth coloring.
+    fn degree(&self) -> f64 {
+        2.
+    }
+
+    /// Order of the rotational symmetry of the set around 0, 1 if there is
+    /// none.
+    fn symmetry(&self) -> u32 {
+        1
+    }
+
+    /// Order of the rotational symmetry of the Julia sets of this formula.
+    fn julia_symmetry(&self) -> u32 {
+        1
+    }
+
+    /// Whether the points converge to an attractor instead of escaping : the
+    /// iterations then also stop when `z` stops moving.
+    fn converges(&self) -> bool {
+        false
+    }
+
+    /// The attractor a converged orbit reached, for the root coloring.
+    fn root(&self, _z: c64) -> Option<u8> {
+        None
+    }
+
+    /// The points `z` such that `step(z, c) = w`, for the inverse iteration
+    /// methods. Empty when they aren't known.
+    fn preimages(&self, _w: c64, _c: c64) -> Vec<c64> {
+        Vec::new()
+    }
+}
+
+/// The exponent `d` of `z = z^d + c`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Power {
+    /// Computed with repeated multiplications.
+    Integer(u32),
+    /// Computed in polar form.
+    Real(f64),
+    /// Computed as `exp(d*ln(z))`.
+    Complex(c64),
+}
+
+impl Default for Power {
+    fn default() -> Power {
+        Power::Integer(2)
+    }
+}
+
+impl Power {
+    //"3", "2.5" or "2,0.1"
+    pub fn from_string(s: &str) -> Option<Power> {
+        if s.contains(',') {
+            return complex_from_string(s).map(Power::Complex);
+        }
+        if let Ok(d) = s.parse::<u32>() {
+            if d >= 2 {
+                return Some(Power::Integer(d));
+            }
+        }
+        match s.parse::<f64>() {
+            Ok(d) if d > 1. => Some(Power::Real(d)),
+            _               => {
+                println!("Error : invalid power, it must be a number greater than 1, or a complex number 're,im'.");
+                None
+            }
+        }
+    }
+
+    pub fn apply(&self, z: c64) -> c64 {
+        match *self {
+            Power::Integer(2) => z*z,
+            Power::Integer(d) => cmath::powi(z, d),
+            Power::Real(d)    => cmath::powf(z, d),
+            Power::Complex(d) => cmath::powc(z, d),
+        }
+    }
+
+    pub fn degree(&self) -> f64 {
+        match *self {
+            Power::Integer(d) => d as f64,
+            Power::Real(d)    => d,
+            Power::Complex(d) => d.abs(),
+        }
+    }
+}
+
+/// The variants of `z^d + c` that fold or mirror `z` at every iteration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    /// `z^d + c`, the Mandelbrot set.
+    Standard,
+    /// `(|Re z| + i|Im z|)^d + c`.
+    BurningShip,
+    /// `conj(z)^d + c`, also known as the Mandelbar set.
+    Tricorn,
+    /// `|Re(z^d)| + i*Im(z^d) + c`.
+    Celtic,
+    /// `|Re(z^d)| + i*|Im(z^d)| + c`.
+    Buffalo,
+}
+
+fn fold(z: c64) -> c64 {
+    c64::new(z.re().abs(), z.im().abs())
+}
+
+/// The built-in formulas, `z = z^d + c` and its variants, with `z` starting
+/// at 0 and `c` being the pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Builtin {
+    pub variant: Variant,
+    pub power: Power,
+}
+
+impl Builtin {
+    pub fn mandelbrot() -> Builtin {
+        Builtin {
+            variant: Variant::Standard,
+            power: Power::Integer(2),
+        }
+    }
+}
+
+impl Fractal for Builtin {
+    fn name(&self) -> &str {
+        match self.variant {
+            Variant::Standard    => "mandelbrot",
+            Variant::BurningShip => "burning-ship",
+            Variant::Tricorn     => "tricorn",
+            Variant::Celtic      => "celtic",
+            Variant::Buffalo     => "buffalo",
+        }
+    }
+
+    fn init(&self, pixel: c64) -> (c64, c64) {
+        (c64::new(0., 0.), pixel)
+    }
+
+    fn step(&self, z: c64, c: c64, _pixel: c64) -> c64 {
+        match self.variant {
+            Variant::Standard    => self.power.apply(z) + c,
+            Variant::BurningShip => self.power.apply(fold(z)) + c,
+            Variant::Tricorn     => self.power.apply(z.conj()) + c,
+            Variant::Celtic      => {
+                let z = self.power.apply(z);
+                c64::new(z.re().abs(), z.im()) + c
+            },
+            Variant::Buffalo     => fold(self.power.apply(z)) + c,
+        }
+    }
+
+    /// The main cardioid and the period 2 bulb of the Mandelbrot set.
+    fn inside(&self, pixel: c64) -> bool {
+        if self.variant != Variant::Standard || self.power != Power::Integer(2) {
+            return false;
+        }
+        let (x, y) = (pixel.re(), pixel.im());
+        let q = (x - 0.25)*(x - 0.25) + y*y;
+        q*(q + x - 0.25) <= y*y/4. || (x + 1.)*(x + 1.) + y*y <= 1./16.
+    }
+
+    fn default_plan(&self) -> Plan {
+        if self.power != Power::Integer(2) {
+            return fit_plan(self);
+        }
+        let (up, left) = match self.variant {
+            Variant::Standard    => return Plan::default(),
+            Variant::BurningShip => (-2., -2.5),
+            Variant::Tricorn     => (-1.5, -2.75),
+            Variant::Celtic      => (-1.5, -3.),
+            Variant::Buffalo     => (-1.875, -2.875),
+        };
+        Plan {
+            up,
+            left,
+            width: 4.5,
+            height: 3.,
+        }
+    }
+
+    fn julia_plan(&self) -> Option<Plan> {
+        if self.variant == Variant::Standard && self.power == Power::Integer(2) {
+            Some(Plan {
+                up: -1.2,
+                left: -1.8,
+                width: 3.6,
+                height: 2.4,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn degree(&self) -> f64 {
+        self.power.degree()
+    }
+
+    /// With an integer `d`, the Mandelbrot-like set is unchanged by a
+    /// `2*pi/(d - 1)` turn and the Tricorn by a `2*pi/(d + 1)` turn.
+    fn symmetry(&self) -> u32 {
+        match (self.variant, self.power) {
+            (Variant::Standard, Power::Integer(d)) => d - 1,
+            (Variant::Tricorn, Power::Integer(d))  => d + 1,
+            _                                      => 1,
+        }
+    }
+
+    /// The `d` roots of `w - c`, conjugated for the Tricorn.
+    fn preimages(&self, w: c64, c: c64) -> Vec<c64> {
+        let d = match self.power {
+            Power::Integer(d) => d,
+            _                 => return Vec::new(),
+        };
+        let w = w - c;
+        let modulus = w.abs().powf(1./d as f64);
+        let roots = (0..d).map(|k| c64::from_polar(modulus, (w.arg() + 2.*k as f64*::std::f64::consts::PI)/d as f64));
+        match self.variant {
+            Variant::Standard => roots.collect(),
+            Variant::Tricorn  => roots.map(|root| root.conj()).collect(),
+            _                 => Vec::new(),
+        }
+    }
+
+    /// The Julia sets of `z^d + c` are unchanged by a `2*pi/d` turn.
+    fn julia_symmetry(&self) -> u32 {
+        match (self.variant, self.power) {
+            (Variant::Standard, Power::Integer(d)) => d,
+            _                                      => 1,
+        }
+    }
+}
+
+/// The Julia set of a formula for a fixed `c` : `z` starts at the pixel.
+pub struct Julia {
+    pub fractal: Arc<dyn Fractal>,
+    pub c: c64,
+    name: String,
+}
+
+impl Julia {
+    pub fn new(fractal: Arc<dyn Fractal>, c: c64) -> Julia {
+        let name = format!("{}-julia", fractal.name());
+        Julia {
+            fractal,
+            c,
+            name,
+        }
+    }
+}
+
+impl Fractal for Julia {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn init(&self, pixel: c64) -> (c64, c64) {
+        (pixel, self.c)
+    }
+
+    fn step(&self, z: c64, c: c64, pixel: c64) -> c64 {
+        self.fractal.step(z, c, pixel)
+    }
+
+    fn escaped(&self, z: c64) -> bool {
+        self.fractal.escaped(z)
+    }
+
+    fn default_plan(&self) -> Plan {
+        self.fractal.julia_plan().unwrap_or_else(|| fit_plan(self))
+    }
+
+    fn degree(&self) -> f64 {
+        self.fractal.degree()
+    }
+
+    fn symmetry(&self) -> u32 {
+        self.fractal.julia_symmetry()
+    }
+
+    fn converges(&self) -> bool {
+        self.fractal.converges()
+    }
+
+    fn root(&self, z: c64) -> Option<u8> {
+        self.fractal.root(z)
+    }
+
+    fn preimages(&self, w: c64, c: c64) -> Vec<c64> {
+        self.fractal.preimages(w, c)
+    }
+}
+
+/// The settings of the command line a formula may use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameters {
+    /// `--power`, 2 by default.
+    pub power: Power,
+    /// `--polynomial`, `z^3 - 1` by default.
+    pub polynomial: Polynomial,
+    /// `--relaxation`, 1 by default.
+    pub relaxation: c64,
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters {
+            power: Power::default(),
+            polynomial: Polynomial::default(),
+            relaxation: c64::new(1., 0.),
+        }
+    }
+}
+
+/// Builds a formula from the parameters, which it may ignore.
+pub type Constructor = Box<dyn Fn(&Parameters) -> Box<dyn Fractal> + Send + Sync>;
+
+/// The formulas available by name.
+pub struct Registry {
+    fractals: Vec<(String, Constructor)>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            fractals: Vec::new(),
+        }
+    }
+
+    /// A registry holding `mandelbrot`, `burning-ship`, `tricorn`, `celtic`,
+    /// `buffalo`, `newton` and `nova`.
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::new();
+        for &variant in &[Variant::Standard, Variant::BurningShip, Variant::Tricorn, Variant::Celtic, Variant::Buffalo] {
+            let name = Builtin { variant, power: Power::default() }.name().to_string();
+            registry.register(&name, move |parameters| Box::new(Builtin { variant, power: parameters.power }));
+        }
+        registry.register("newton", |parameters| Box::new(Newton::new(parameters.polynomial.clone(), parameters.relaxation)));
+        registry.register("nova", |parameters| Box::new(Nova::new(parameters.polynomial.clone(), parameters.relaxation)));
+        registry
+    }
+
+    /// Adds a formula, replacing any formula with the same name.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+        where F: Fn(&Parameters) -> Box<dyn Fractal> + Send + Sync + 'static {
+        self.fractals.retain(|(registered, _)| registered != name);
+        self.fractals.push((name.to_string(), Box::new(constructor)));
+    }
+
+    pub fn create(&self, name: &str, parameters: &Parameters) -> Option<Arc<dyn Fractal>> {
+        self.fractals.iter()
+            .find(|&(registered, _)| registered == name)
+            .map(|(_, constructor)| Arc::from(constructor(parameters)))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.fractals.iter().map(|(name, _)| &name[..]).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::with_builtins()
+    }
+}
+
+/// Finds a 3:2 frame around the set by sampling it coarsely. Sets with a
+/// rotational symmetry are centered on 0.
+pub fn fit_plan<F: Fractal + ?Sized>(fractal: &F) -> Plan {
+    const SAMPLES: usize = 240;
+    const EXTENT: f64 = 3.;
+    let step = 2.*EXTENT/SAMPLES as f64;
+    let (mut min_re, mut max_re, mut min_im, mut max_im) = (EXTENT, -EXTENT, EXTENT, -EXTENT);
+    for j in 0..SAMPLES {
+        for i in 0..SAMPLES {
+            let pixel = c64::new(i as f64*step - EXTENT, j as f64*step - EXTENT);
+            if escape_time(pixel, fractal, 64).iter == 64 {
+                min_re = min_re.min(pixel.re());
+                max_re = max_re.max(pixel.re() + step);
+                min_im = min_im.min(pixel.im());
+                max_im = max_im.max(pixel.im() + step);
+            }
+        }
+    }
+    if min_re > max_re {
+        return Plan::default();
+    }
+    //a half turn only needs both axes centered, other turns a square
+    let symmetry = fractal.symmetry();
+    if symmetry == 2 {
+        max_re = min_re.abs().max(max_re.abs());
+        min_re = -max_re;
+        max_im = min_im.abs().max(max_im.abs());
+        min_im = -max_im;
+    } else if symmetry > 2 {
+        let radius = min_re.abs().max(max_re.abs()).max(min_im.abs()).max(max_im.abs());
+        min_re = -radius;
+        max_re = radius;
+        min_im = -radius;
+        max_im = radius;
+    }
+    //10% margin, then widen one side to get the 3:2 ratio
+    let width = (max_re - min_re)*1.1;
+    let height = (max_im - min_im)*1.1;
+    let (width, height) = if width < height*1.5 { (height*1.5, height) } else { (width, width/1.5) };
+    Plan {
+        up: (min_im + max_im - height)/2.,
+        left: (min_re + max_re - width)/2.,
+        width,
+        height,
+    }
+}
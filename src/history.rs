@@ -0,0 +1,198 @@
+//! The views visited in the viewer, with back and forward like a browser.
+//!
+//! The history file is a text file : the fractal on the first line, as the
+//! options that make it, then the index of the current view, then one view
+//! per line as `zoom_lvl up,left,width,height`, the oldest first.
+
+use std::io::{self, BufRead, Write};
+use plan::Plan;
+use raw::invalid_data;
+
+/// The oldest views are forgotten past this length.
+const MAX_LEN: usize = 100;
+
+/// A visited view, with the zoom level its max_iter depends on.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub plan: Plan,
+    pub zoom_lvl: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    fractal: String,
+    locations: Vec<Location>,
+    current: usize,
+}
+
+impl History {
+    /// A history of the views of `fractal`, starting at `location`.
+    /// `fractal` is any single line telling the fractals apart, like their
+    /// command line options.
+    pub fn new(fractal: &str, location: Location) -> History {
+        History {
+            fractal: fractal.to_string(),
+            locations: vec![location],
+            current: 0,
+        }
+    }
+
+    pub fn fractal(&self) -> &str {
+        &self.fractal
+    }
+
+    pub fn current(&self) -> &Location {
+        &self.locations[self.current]
+    }
+
+    /// Goes to a new view, forgetting the views after the current one.
+    pub fn visit(&mut self, location: Location) {
+        self.locations.truncate(self.current + 1);
+        self.locations.push(location);
+        if self.locations.len() > MAX_LEN {
+            self.locations.remove(0);
+        }
+        self.current = self.locations.len() - 1;
+    }
+
+    /// Changes the current view without adding one, when the window is
+    /// resized.
+    pub fn replace(&mut self, location: Location) {
+        self.locations[self.current] = location;
+    }
+
+    /// The previous view, `None` at the oldest one.
+    pub fn back(&mut self) -> Option<&Location> {
+        if self.current == 0 {
+            return None;
+        }
+        self.current -= 1;
+        Some(&self.locations[self.current])
+    }
+
+    /// The next view, `None` at the newest one.
+    pub fn forward(&mut self) -> Option<&Location> {
+        if self.current + 1 >= self.locations.len() {
+            return None;
+        }
+        self.current += 1;
+        Some(&self.locations[self.current])
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}", self.fractal)?;
+        writeln!(writer, "{}", self.current)?;
+        for location in &self.locations {
+            let plan = &location.plan;
+            writeln!(writer, "{} {},{},{},{}", location.zoom_lvl, plan.up, plan.left, plan.width, plan.height)?;
+        }
+        Ok(())
+    }
+
+    /// Reads back a file written by `write`.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<History> {
+        let mut lines = reader.lines();
+        let fractal = lines.next().unwrap_or_else(|| Err(invalid_data("empty history file")))?;
+        let current = lines.next()
+            .unwrap_or_else(|| Err(invalid_data("missing current view")))?
+            .trim().parse().map_err(|_| invalid_data("invalid current view"))?;
+        let mut locations = Vec::new();
+        for line in lines {
+            let line = line?;
+            if !line.trim().is_empty() {
+                locations.push(parse_location(&line).ok_or_else(|| invalid_data(&format!("invalid view `{}`", line)))?);
+            }
+        }
+        if current >= locations.len() {
+            return Err(invalid_data("the current view is out of the history"));
+        }
+        Ok(History {
+            fractal,
+            locations,
+            current,
+        })
+    }
+}
+
+//"3 -0.75,-0.1,0.03,0.02"
+fn parse_location(s: &str) -> Option<Location> {
+    let mut parts = s.split_whitespace();
+    let zoom_lvl = parts.next()?.parse().ok()?;
+    let plan = Plan::parse(parts.next()?).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Location {
+        plan,
+        zoom_lvl,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn location(zoom_lvl: u32) -> Location {
+        Location {
+            plan: Plan {up: -1. + zoom_lvl as f64, left: -2., width: 3., height: 2.},
+            zoom_lvl,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut history = History::new("--power 3", location(0));
+        history.visit(location(1));
+        history.visit(location(2));
+        history.back();
+        let mut bytes = Vec::new();
+        history.write(&mut bytes).unwrap();
+
+        let mut read = History::read(Cursor::new(bytes)).unwrap();
+        assert_eq!(read.fractal(), "--power 3");
+        assert_eq!(read.current().zoom_lvl, 1);
+        assert_eq!(read.current().plan.up, 0.);
+        assert_eq!(read.forward().map(|location| location.zoom_lvl), Some(2));
+        assert!(read.forward().is_none());
+    }
+
+    #[test]
+    fn visiting_forgets_the_forward_views() {
+        let mut history = History::new("", location(0));
+        history.visit(location(1));
+        history.visit(location(2));
+        assert_eq!(history.back().map(|location| location.zoom_lvl), Some(1));
+        assert_eq!(history.back().map(|location| location.zoom_lvl), Some(0));
+        assert!(history.back().is_none());
+        assert_eq!(history.forward().map(|location| location.zoom_lvl), Some(1));
+
+        history.visit(location(5));
+        assert!(history.forward().is_none());
+        assert_eq!(history.back().map(|location| location.zoom_lvl), Some(1));
+        assert_eq!(history.back().map(|location| location.zoom_lvl), Some(0));
+    }
+
+    #[test]
+    fn oldest_views_are_evicted() {
+        let mut history = History::new("", location(0));
+        for zoom_lvl in 1..MAX_LEN as u32 + 10 {
+            history.visit(location(zoom_lvl));
+        }
+        assert_eq!(history.current().zoom_lvl, MAX_LEN as u32 + 9);
+        let mut oldest = history.current().zoom_lvl;
+        let mut count = 1;
+        while let Some(location) = history.back() {
+            oldest = location.zoom_lvl;
+            count += 1;
+        }
+        assert_eq!(count, MAX_LEN);
+        assert_eq!(oldest, 10);
+    }
+
+    #[test]
+    fn current_view_out_of_the_history_is_invalid() {
+        let err = History::read(Cursor::new("\n3\n0 -1,-2,3,2\n")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,116 @@
+//! The inverse iteration methods plot a Julia set directly : the Julia set
+//! repels the orbits of `z = f(z)`, so it attracts those of the preimages.
+//! They show the dendrites and the dusts that look empty with escape times.
+
+use std::collections::VecDeque;
+use complex::*;
+use plan::{Plan, ImageDim};
+use fractal::Fractal;
+use buddhabrot::Rng;
+use buffer::DensityBuffer;
+
+/// The first preimages are skipped, the orbit isn't on the Julia set yet.
+const SKIPPED: u32 = 64;
+
+/// The finest grid the modified method counts its visits in, per side.
+const MAX_GRID: usize = 4096;
+
+/// How the `generate` command computes a Julia set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    EscapeTime,
+    /// Random walk on the preimages, the inverse iteration method.
+    Inverse,
+    /// All the preimages, but not in the cells already visited enough.
+    ModifiedInverse,
+}
+
+impl Method {
+    pub fn from_string(s: &str) -> Option<Method> {
+        match s {
+            "escape" => Some(Method::EscapeTime),
+            "iim"    => Some(Method::Inverse),
+            "miim"   => Some(Method::ModifiedInverse),
+            _        => {
+                println!("Error : invalid method, it must be 'escape', 'iim' or 'miim'.");
+                None
+            }
+        }
+    }
+}
+
+/// Follows `points` random preimages from a point of the Julia set of `c`,
+/// counting them in the pixels of the Plan. The points are dense where the
+/// preimages accumulate and sparse on the tips of the dendrites.
+pub fn inverse_iteration(fractal: &dyn Fractal, c: c64, plan: &Plan, image_dim: &ImageDim, points: u64, seed: u64) -> DensityBuffer {
+    let mut density = DensityBuffer::with_size(image_dim.width, image_dim.height);
+    let mut rng = Rng::new(seed);
+    let mut z = c64::new(1., 0.);
+    for point in 0..points + SKIPPED as u64 {
+        z = match random_preimage(fractal, z, c, &mut rng) {
+            Some(preimage) => preimage,
+            None           => break,
+        };
+        if point >= SKIPPED as u64 {
+            plot(&mut density, plan, z);
+        }
+    }
+    density
+}
+
+/// Visits the tree of the preimages up to `max_depth`, pruning the branches
+/// whose point lands in a cell already visited `limit` times : the whole Julia
+/// set gets drawn with an even density. The cells are the
+/// pixels, unless the Plan is zoomed so far that they would be too many.
+pub fn modified_inverse_iteration(fractal: &dyn Fractal, c: c64, plan: &Plan, image_dim: &ImageDim, max_depth: u32, limit: u32) -> DensityBuffer {
+    let mut density = DensityBuffer::with_size(image_dim.width, image_dim.height);
+    //the visits are counted around the whole Julia set, in the view or not
+    let radius = c.abs().max(2.);
+    let cell = (plan.width/image_dim.width as f64).max(2.*radius/MAX_GRID as f64);
+    let side = (2.*radius/cell).ceil() as usize + 1;
+    let mut visits = DensityBuffer::with_size(side, side);
+
+    let mut rng = Rng::new(0);
+    let mut start = c64::new(1., 0.);
+    for _ in 0..SKIPPED {
+        start = match random_preimage(fractal, start, c, &mut rng) {
+            Some(preimage) => preimage,
+            None           => return density,
+        };
+    }
+
+    //breadth first, so that the first visits of a cell come from the short
+    //branches, which spread over the whole set before the long ones
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+    while let Some((z, depth)) = queue.pop_front() {
+        let x = ((z.re() + radius)/cell).floor();
+        let y = ((z.im() + radius)/cell).floor();
+        if x < 0. || y < 0. || x >= side as f64 || y >= side as f64 || visits.at(x as usize, y as usize) >= limit {
+            continue;
+        }
+        visits.add(x as usize, y as usize);
+        plot(&mut density, plan, z);
+        if depth < max_depth {
+            queue.extend(fractal.preimages(z, c).into_iter().map(|preimage| (preimage, depth + 1)));
+        }
+    }
+    density
+}
+
+fn random_preimage(fractal: &dyn Fractal, z: c64, c: c64, rng: &mut Rng) -> Option<c64> {
+    let preimages = fractal.preimages(z, c);
+    if preimages.is_empty() {
+        return None;
+    }
+    let index = (rng.next_u64() % preimages.len() as u64) as usize;
+    Some(preimages[index])
+}
+
+fn plot(density: &mut DensityBuffer, plan: &Plan, z: c64) {
+    let x = (z.re() - plan.left)/plan.width*density.width as f64;
+    let y = (z.im() - plan.up)/plan.height*density.height as f64;
+    if x >= 0. && y >= 0. && x < density.width as f64 && y < density.height as f64 {
+        density.add(x as usize, y as usize);
+    }
+}
@@ -0,0 +1,17 @@
+extern crate complex;
+
+pub mod plan;
+pub mod buffer;
+pub mod cmath;
+pub mod render;
+pub mod fractal;
+pub mod expr;
+pub mod newton;
+pub mod buddhabrot;
+pub mod iim;
+pub mod history;
+pub mod bookmark;
+pub mod palette;
+pub mod raw;
+pub mod encode;
+mod zlib;
@@ -1,15 +1,29 @@
+#[cfg(feature = "viewer")]
 extern crate sfml;
 extern crate complex;
-
-use sfml::graphics::{Texture, Sprite, RenderWindow, RenderTarget, Image, Color, RectangleShape, Shape, Transformable};
-use sfml::system::Vector2f;
-use sfml::window::{ContextSettings, VideoMode, event};
-use sfml::window::mouse::MouseButton;
-use sfml::window::WindowStyle;
-use complex::*;
+extern crate mandelbrot;
+
+#[cfg(feature = "viewer")]
+mod viewer;
+
+use mandelbrot::plan::{Plan, ImageDim, complex_from_string};
+use mandelbrot::buffer::{Color, PixelArrayBuffer, IterationBuffer, FloatPixelBuffer};
+use mandelbrot::render::{compute_iterations, DEFAULT_THREADS};
+use mandelbrot::fractal::{Fractal, Julia, Parameters, Power, Registry};
+use mandelbrot::expr::{Expression, constant};
+use mandelbrot::newton::Polynomial;
+use mandelbrot::buddhabrot::{ToneMap, compute_density, tone_map};
+use mandelbrot::iim::{Method, inverse_iteration, modified_inverse_iteration};
+use mandelbrot::palette::{Palette, Coloring, colorize_f32};
+use mandelbrot::raw::{write_raw, write_npy, read_raw};
+use mandelbrot::encode::{ImageFormat, save_image, save_float_image};
+use std::collections::HashMap;
 use std::env;
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 //use std::mem::transmute;
 
 fn main() {
@@ -20,9 +34,11 @@ fn main() {
         match &(*args[1]) {
             "generate" => generate_mandelbrot(args),
             "draw"     => draw_main(args),
+            "colorize" => colorize_main(args),
+            "buddhabrot" => buddhabrot_main(args),
             "help"     => {
                 if args.len() >= 3 {
-                    print_help(&(*args[2]));
+                    print_help(&args[2]);
                 } else {
                     print_help("");
                 }
@@ -30,647 +46,506 @@ fn main() {
             _          => print_help(""),
         }
     }
-    println!("");
-}
-
-#[derive(Debug, Clone)]
-struct Plan {
-    up: f64,
-    left: f64,
-    width: f64,
-    height: f64,
+    println!();
 }
 
-impl Plan {
-    //"2,1,3,4"
-    fn from_string(s: &String) -> Option<Plan> {
-        if &(*s) == "?" {
-            return Some(Plan {
-                up: -1.,
-                left: -2.,
-                width: 3.,
-                height: 2.,
-            });
+fn print_help(category: &str) {
+    println!();
+    match category {
+        "generate" => {
+            println!("RUST-MANDELBROT : GENERATE");
+            println!("--------------------------\n");
+            println!("Synopsis : generate [plan] [image] [max_iter] [file] [options]\n");
+            println!("  plan     : the frame of the mandelbrot set you want to draw. It must be of the form 'up,left,width,height'. If you want the default settings (that are '-1,-2,3,2'), just type '?'.");
+            println!("  image    : the image size, in pixels. It must match the following pattern : 'width,height'. If you want the default size (that is '900, 600'), type '?'.");
+            println!("  max_iter : the max iterations used to determine the set's points. If you don't know which value you should use, prefer a number around 100.");
+            println!("  file     : the output file to write the image. The format will be guessed from the extension. Supported fromats are : png, bmp, ppm, pgm and hdr, plus tga and jpg through SFML.");
+            println!("  --format : 'image' (default) writes the colored image, 'raw' writes the escape counts and smooth values in the raw binary format, 'npy' writes them as a NumPy array. Files ending in .raw or .npy get the matching format.");
+            println!("  --palette   : the palette, either 'default', 'cyclic', 'grayscale', 'fire', 'ocean' or a list of colors like '000000,ff8800,ffffff'.");
+            println!("  --coloring  : how the pixels are colored : 'iterations' (default), 'smooth', 'histogram' or 'roots' (for 'newton', one color per root, darker when it takes longer to reach).");
+            println!("  --depth     : the bits per channel of the image, 8 (default) or 16. 16 bits are supported for png only. hdr images always keep float colors.");
+            println!("  --fractal   : the fractal to draw : 'mandelbrot' (default), 'burning-ship', 'tricorn', 'celtic', 'buffalo', 'newton' or 'nova'. Each one has its own default frame when the plan is '?'.");
+            println!("  --formula   : iterate this formula instead, like 'z^3 - z + c' or 'sin(z)*c'. It can use z, c, pixel, i, pi, e, + - * / ^ and exp, log, sin, cos, abs, conj. z starts at c, or at the value given before a ';' like in '0; z^2 + c'.");
+            println!("  --julia     : draw the Julia set of this c, given as 're,im', instead of the Mandelbrot set. With '?' as plan, the default frame fits the Julia set.");
+            println!("  --power     : the exponent d of z = z^d + c, 2 by default. It can be an integer, a real number like '2.5' or a complex number like '2,0.1'. With '?' as plan, the default frame fits the set.");
+            println!("  --polynomial : the polynomial p of the 'newton' (z = z - a*p(z)/p'(z)) and 'nova' (the same plus c) fractals, as its coefficients from the highest degree, like '1,0,0,-1' (the default) for z^3 - 1. Coefficients can be complex, like '2*i'.");
+            println!("  --relaxation : the factor a of the 'newton' and 'nova' fractals, 1 by default.");
+            println!("  --keep-data : also write the iteration data to this file, in the raw format, so it can be colored again with `colorize`.");
+            println!("  --method    : how a Julia set is computed : 'escape' (default) for escape times, 'iim' to plot random preimages of its points, or 'miim' to plot all of them up to a depth of max_iter. The preimages show the dendrites and dusts that escape times leave empty. They need `--julia` with 'mandelbrot' or 'tricorn' and an integer power, and only write images.");
+            println!("  --samples   : the number of points plotted by 'iim', 10 per pixel by default.");
+            println!("  --density   : how many times 'miim' may visit a pixel, 1 by default. Higher values draw a thicker set.");
+            println!("--------------------------");
+        },
+        "colorize" => {
+            println!("RUST-MANDELBROT : COLORIZE");
+            println!("--------------------------\n");
+            println!("Synopsis : colorize [data] [file] [options]\n");
+            println!("  data        : a raw iteration file, written by `generate` with `--format raw` or `--keep-data`.");
+            println!("  file        : the output file to write the image. The format will be guessed from the extension. Supported fromats are : png, bmp, ppm, pgm and hdr, plus tga and jpg through SFML.");
+            println!("  --palette   : the palette, like for `generate`.");
+            println!("  --coloring  : the coloring mode, like for `generate`.");
+            println!("  --depth     : the bits per channel, like for `generate`.");
+            println!("--------------------------");
+        },
+        "buddhabrot" => {
+            println!("RUST-MANDELBROT : BUDDHABROT");
+            println!("----------------------------\n");
+            println!("Synopsis : buddhabrot [plan] [image] [max_iter] [file] [options]\n");
+            println!("  plan        : the frame of the image, like for `generate`.");
+            println!("  image       : the image dimensions, like for `generate`.");
+            println!("  max_iter    : orbits escaping within this many iterations are traced. Three limits 'red,green,blue', like '5000,500,50', draw a Nebulabrot with one limit per channel.");
+            println!("  file        : the output file, like for `generate`.");
+            println!("  --samples   : the number of random points traced, 10 per pixel by default.");
+            println!("  --seed      : the seed of the random points, to render the same image again. It is printed when not given.");
+            println!("  --threads   : the number of threads, 4 by default. The image depends on it for a given seed.");
+            println!("  --tone      : how the orbit counts are turned into brightness : 'linear' (default), 'sqrt' or 'log'.");
+            println!("  --clip      : the percentile of the counts shown as white, 99.9 by default. Lower values make the image brighter.");
+            println!("  --depth     : the bits per channel, like for `generate`.");
+            println!("  --fractal, --formula, --julia, --power : the fractal, like for `generate`.");
+            println!("--------------------------");
+        },
+        "draw" => {
+            println!("RUST-MANDELBROT : DRAW");
+            println!("----------------------\n");
+            println!("Synopsis : draw [plan] [window] [max_iter] [options]\n");
+            println!("  plan     : the frame of the mandelbrot set you want to use as default view. When you will right-click, it will bring you to this view. It must be of the form 'up,left,width,height'. If you want the default settings (that are '-1,-2,3,2'), just type '?'.");
+            println!("  window   : the size of the window, in pixels. It must match the following pattern : 'width,height'. If you want the default size (that is '900, 600'), type '?'. The window can then be resized, keeping the center and the scale of the view.");
+            println!("  max_iter : the max iterations used to determine the set's points. If you don't know which value you should use, prefer a number around 100.");
+            println!("  --palette  : the palette, like for `generate`.");
+            println!("  --coloring : the coloring mode, like for `generate`.");
+            println!("  --fractal  : the fractal to explore, like for `generate`.");
+            println!("  --formula  : iterate this formula, like for `generate`.");
+            println!("  --julia    : explore the Julia set of this c, like for `generate`.");
+            println!("  --power    : the exponent of z = z^d + c, like for `generate`.");
+            println!("  --polynomial, --relaxation : the Newton and Nova settings, like for `generate`.");
+            println!("  --font     : the TrueType font of the labels. A system font is used by default, and without one the labels are printed here.");
+            println!("  --zoom-factor : how much one tick of the mouse wheel zooms, 1.25 by default.");
+            println!("  --snapshot : the size of the snapshots taken with S, either a multiple of the window like '4x' (the default) or 'width,height' like '7680,4320'.");
+            println!("  --bookmark : start at this bookmark, with its max_iter and fractal. Only the window size is given then, and may be left out.");
+            println!("\nMouse :");
+            println!("  left-click         : zoom in on the rectangle around the cursor.");
+            println!("  left-drag          : zoom in on the rectangle dragged out, with the shape of the window.");
+            println!("  shift + left-click : zoom out by the same factor, around the cursor.");
+            println!("  middle-drag        : move the view.");
+            println!("  wheel              : zoom in / out by the zoom factor, keeping the point under the cursor in place.");
+            println!("  right-click        : go back to the original view, like Home.");
+            println!("\nKeys :");
+            println!("  arrows   : move the view by a tenth of the window.");
+            println!("  B / F    : go back / forward in the views visited. The history is kept in `~/.mandelbrot_history` for the next session on the same fractal, with the same options.");
+            println!("  Home     : go back to the original view.");
+            println!("  K        : save the view as a bookmark in `~/.mandelbrot_bookmarks`, typing its name then Enter.");
+            println!("  L        : list the bookmarks, to jump to one with the arrows and Enter.");
+            println!("  S        : render the view at the snapshot size in the background, to `snapshot-[date]-[time].png`. The image keeps the `generate` command that renders it again.");
+            println!("  H        : show / hide the coordinates of the cursor, the center and width of the view, the zoom, the max iterations and the render time.");
+            println!("  C        : start / stop the palette cycling.");
+            println!("  + / -    : speed up / slow down the cycling.");
+            println!("  R        : reverse the cycling direction.");
+            println!("  I        : show / hide the Julia set of the point under the cursor, in the top right corner.");
+            println!("  J        : open this Julia set in the whole window, and press again to come back.");
+            println!("  LControl : hold it to draw the orbit of the point under the cursor, with its iterations, escape and period.");
+            println!("--------------------------");
         }
-        let mut plan = Plan {
-            up: 0.,
-            left: 0.,
-            width: 0.,
-            height: 0.,
-        };
-        let coords: Vec<&str> = s.split(',').collect();
-        if coords.len() != 4 {
-            println!("Error : invalid Plan format, it must match 'x,y,w,h'.");
-            return None;
-        } else {
-            plan.up = match coords[0].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
-            plan.left = match coords[1].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
-            plan.width = match coords[2].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
-            plan.height = match coords[3].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
+        _ => {
+            println!("RUST-MANDELBROT : USE");
+            println!("---------------------\n\nYou must specify a command while calling the programm :");
+            println!("  help        Print this help.");
+            println!("  generate    Generate a Mandelbrot set and save it to an image.");
+            println!("  draw        Launch the interactive drawer.");
+            println!("  colorize    Color iteration data saved by `generate`.");
+            println!("  buddhabrot  Render the Buddhabrot or a Nebulabrot to an image.");
+            println!("---------------------\nType `help [command]` to get more specific help about a command.");
+            println!("\nYou don't know about Mandelbrot's set ? Just run with arguments 'draw ? ? 100' :)");
         }
-        Some(plan)
     }
 }
 
-#[derive(Debug, Clone)]
-struct ImageDim {
-    width: usize,
-    height: usize,
+/// Prints an error about a file that couldn't be read or written and exits
+/// with a failure status, so that scripts notice the missing output.
+fn exit_with_error(message: &str) -> ! {
+    println!("Error : {}", message);
+    process::exit(1);
 }
 
-impl ImageDim {
-    fn from_string(s: &String) -> Option<ImageDim> {
-        if &(*s) == "?" {
-            return Some(ImageDim {
-                width: 900,
-                height: 600,
-            });
+/// Saves the pixels with the built-in encoders, or with SFML for the formats
+/// they don't handle (tga and jpg).
+fn save_pixels(pixel_buffer: &PixelArrayBuffer, path: &str) {
+    if ImageFormat::from_path(path).is_some() {
+        if let Err(err) = save_image(pixel_buffer, path) {
+            exit_with_error(&format!("couldn't write `{}` : {}", path, err));
         }
-        let mut img = ImageDim {
-            width: 0,
-            height: 0,
-        };
-        let coords: Vec<&str> = s.split(',').collect();
-        if coords.len() != 2 {
-            println!("Error : invalid Image Dim format, it must match 'w,h'.");
-            return None;
-        } else {
-            img.width = match coords[0].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
-            img.height = match coords[1].parse() {
-                Ok(coord) => coord,
-                Err(_)    => {
-                    println!("Error : invalid Plan format, only numbers are accepted.");
-                    return None;
-                }
-            };
-        }
-        Some(img)
+    } else {
+        save_with_sfml(pixel_buffer, path);
     }
 }
 
-#[derive(Debug)]
-struct PixelArrayBuffer {
-    buffer: Vec<u8>,
-    pub width: usize,
-    pub height: usize,
-}
-
-impl PixelArrayBuffer {
-    fn with_size(width: usize, height: usize) -> PixelArrayBuffer {
-        PixelArrayBuffer {
-            buffer : vec![255; width*height*4],
-            width: width,
-            height: height,
+/// Saves float colors as they are for the 16 bits and HDR outputs, rounded
+/// to 8 bits for the others.
+fn save_float_pixels(pixel_buffer: &FloatPixelBuffer, depth: u32, path: &str) {
+    if depth == 16 || ImageFormat::from_path(path) == Some(ImageFormat::Hdr) {
+        if let Err(err) = save_float_image(pixel_buffer, path) {
+            exit_with_error(&format!("couldn't write `{}` : {}", path, err));
         }
+    } else {
+        save_pixels(&pixel_buffer.to_pixel_array(), path);
     }
+}
 
-    /*fn at(&self, x: usize, y: usize) -> Color {
-        let color_index = 4*(x*self.height + y);
-        Color::new_rgb(
-            self.buffer[color_index],
-            self.buffer[color_index+1],
-            self.buffer[color_index+2]
-        )
-    }*/
+/// Colors the iteration data and saves it.
+fn save_colored(iterations: &IterationBuffer, palette: &Palette, coloring: Coloring, depth: u32, path: &str) {
+    let set_color = Color::new_rgb(0, 0, 0);
+    save_float_pixels(&colorize_f32(iterations, palette, coloring, &set_color, 0.), depth, path);
+}
 
-    fn get(&self) -> &[u8] {
-        &self.buffer
+#[cfg(feature = "viewer")]
+fn save_with_sfml(pixel_buffer: &PixelArrayBuffer, path: &str) {
+    if !viewer::to_image(pixel_buffer).save_to_file(path) {
+        exit_with_error(&format!("couldn't write `{}`", path));
     }
+}
 
-    fn set(&mut self, x: usize, y: usize, color: &Color) {
-        let color_index = 4*(y*self.width + x);
-        self.buffer[color_index] = color.red;
-        self.buffer[color_index+1] = color.green;
-        self.buffer[color_index+2] = color.blue;
-    }
+#[cfg(not(feature = "viewer"))]
+fn save_with_sfml(_: &PixelArrayBuffer, path: &str) {
+    exit_with_error(&format!("couldn't write `{}` : only png, bmp, ppm and pgm are supported without the `viewer` feature", path));
 }
 
-fn draw_mandelbrot(set_color: &Color, plan: &Plan, image_dim: &ImageDim, max_iter: u32) -> Image {
-    let mut pixel_buffer = Arc::new(Mutex::new(PixelArrayBuffer::with_size(image_dim.width, image_dim.height)));
+#[cfg(feature = "viewer")]
+fn draw_main(args: Vec<String>) {
+    viewer::draw_main(args);
+}
 
-    let half_width = image_dim.width/2;
-    let half_height = image_dim.height/2;
+#[cfg(not(feature = "viewer"))]
+fn draw_main(_: Vec<String>) {
+    println!("Error : the `draw` command isn't available, rebuild with the `viewer` feature to get it");
+}
 
-    let dim_1 = image_dim.clone();
-    let plan_1 = plan.clone();
-    let set_color_1 = *set_color;
-    let buffer_1 = pixel_buffer.clone();
-    let quarter_1 = thread::spawn(move || {
-        let mut non_set_color = Color::new_rgb(0, 0, 0);
-        for i in 0..half_width {
-            for j in 0..half_height {
-                let c = scale(i as i32, j as i32, &dim_1, &plan_1);
-                let mut z = c64::new(0.0, 0.0);
-                let mut iter = 0;
-                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                    iter += 1;
-                    z = z*z + c;
-                }
-                let mut pixel_buffer = buffer_1.lock().unwrap();
-                if iter == max_iter {
-                    pixel_buffer.set(i, j, &set_color_1);
-                } else {
-                    let ratio = iter as f32/max_iter as f32;
-                    if ratio > 0.666 {
-                        let gradient = (255.*ratio) as u8;
-                        non_set_color.red   = 255;
-                        non_set_color.green = 255-gradient;
-                        non_set_color.blue  = 0;
-                    } else if ratio > 0.333 {
-                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                        non_set_color.red   = gradient;
-                        non_set_color.green = gradient;
-                        non_set_color.blue  = 255-gradient;
-                    } else {
-                        let gradient = (3.*255.*ratio) as u8;
-                        non_set_color.red   = 0;
-                        non_set_color.green = 0;
-                        non_set_color.blue  = gradient;
-                    }
-                    pixel_buffer.set(i, j, &non_set_color);
-                }
+/// Splits the arguments into the positional ones and the `--name value`
+/// options. Only the options listed in `allowed` are accepted.
+fn parse_options(args: Vec<String>, allowed: &[&str]) -> Option<(Vec<String>, HashMap<String, String>)> {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--") {
+            let name = name.to_string();
+            if !allowed.contains(&name.as_str()) {
+                println!("Error : unknown option `{}`", arg);
+                return None;
             }
-        }
-    });
-
-    let dim_2 = image_dim.clone();
-    let plan_2 = plan.clone();
-    let set_color_2 = *set_color;
-    let buffer_2 = pixel_buffer.clone();
-    let quarter_2 = thread::spawn(move || {
-        let mut non_set_color = Color::new_rgb(0, 0, 0);
-        for i in half_width..dim_2.width {
-            for j in 0..half_height {
-                let c = scale(i as i32, j as i32, &dim_2, &plan_2);
-                let mut z = c64::new(0.0, 0.0);
-                let mut iter = 0;
-                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                    iter += 1;
-                    z = z*z + c;
-                }
-                let mut pixel_buffer = buffer_2.lock().unwrap();
-                if iter == max_iter {
-                    pixel_buffer.set(i, j, &set_color_2);
-                } else {
-                    let ratio = iter as f32/max_iter as f32;
-                    if ratio > 0.666 {
-                        let gradient = (255.*ratio) as u8;
-                        non_set_color.red   = 255;
-                        non_set_color.green = 255-gradient;
-                        non_set_color.blue  = 0;
-                    } else if ratio > 0.333 {
-                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                        non_set_color.red   = gradient;
-                        non_set_color.green = gradient;
-                        non_set_color.blue  = 255-gradient;
-                    } else {
-                        let gradient = (3.*255.*ratio) as u8;
-                        non_set_color.red   = 0;
-                        non_set_color.green = 0;
-                        non_set_color.blue  = gradient;
-                    }
-                    pixel_buffer.set(i, j, &non_set_color);
+            match args.next() {
+                Some(value) => { options.insert(name, value); },
+                None        => {
+                    println!("Error : the option `{}` requires a value", arg);
+                    return None;
                 }
             }
+        } else {
+            positional.push(arg);
         }
-    });
+    }
+    Some((positional, options))
+}
 
-    let dim_3 = image_dim.clone();
-    let plan_3 = plan.clone();
-    let set_color_3 = *set_color;
-    let buffer_3 = pixel_buffer.clone();
-    let quarter_3 = thread::spawn(move || {
-        let mut non_set_color = Color::new_rgb(0, 0, 0);
-        for i in 0..half_width {
-            for j in half_height..dim_3.height {
-                let c = scale(i as i32, j as i32, &dim_3, &plan_3);
-                let mut z = c64::new(0.0, 0.0);
-                let mut iter = 0;
-                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                    iter += 1;
-                    z = z*z + c;
-                }
-                let mut pixel_buffer = buffer_3.lock().unwrap();
-                if iter == max_iter {
-                    pixel_buffer.set(i, j, &set_color_3);
-                } else {
-                    let ratio = iter as f32/max_iter as f32;
-                    if ratio > 0.666 {
-                        let gradient = (255.*ratio) as u8;
-                        non_set_color.red   = 255;
-                        non_set_color.green = 255-gradient;
-                        non_set_color.blue  = 0;
-                    } else if ratio > 0.333 {
-                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                        non_set_color.red   = gradient;
-                        non_set_color.green = gradient;
-                        non_set_color.blue  = 255-gradient;
-                    } else {
-                        let gradient = (3.*255.*ratio) as u8;
-                        non_set_color.red   = 0;
-                        non_set_color.green = 0;
-                        non_set_color.blue  = gradient;
-                    }
-                    pixel_buffer.set(i, j, &non_set_color);
-                }
-            }
-        }
-    });
+/// Reads the `--palette` and `--coloring` options.
+fn parse_coloring(options: &HashMap<String, String>) -> Option<(Palette, Coloring)> {
+    let palette = match options.get("palette") {
+        Some(palette) => Palette::from_string(palette)?,
+        None          => Palette::default(),
+    };
+    let coloring = match options.get("coloring") {
+        Some(coloring) => Coloring::from_string(coloring)?,
+        None           => Coloring::Iterations,
+    };
+    Some((palette, coloring))
+}
 
-    let dim_4 = image_dim.clone();
-    let plan_4 = plan.clone();
-    let set_color_4 = *set_color;
-    let buffer_4 = pixel_buffer.clone();
-    let quarter_4 = thread::spawn(move || {
-        let mut non_set_color = Color::new_rgb(0, 0, 0);
-        for i in half_width..dim_4.width {
-            for j in half_height..dim_4.height {
-                let c = scale(i as i32, j as i32, &dim_4, &plan_4);
-                let mut z = c64::new(0.0, 0.0);
-                let mut iter = 0;
-                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                    iter += 1;
-                    z = z*z + c;
-                }
-                let mut pixel_buffer = buffer_4.lock().unwrap();
-                if iter == max_iter {
-                    pixel_buffer.set(i, j, &set_color_4);
-                } else {
-                    let ratio = iter as f32/max_iter as f32;
-                    if ratio > 0.666 {
-                        let gradient = (255.*ratio) as u8;
-                        non_set_color.red   = 255;
-                        non_set_color.green = 255-gradient;
-                        non_set_color.blue  = 0;
-                    } else if ratio > 0.333 {
-                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                        non_set_color.red   = gradient;
-                        non_set_color.green = gradient;
-                        non_set_color.blue  = 255-gradient;
-                    } else {
-                        let gradient = (3.*255.*ratio) as u8;
-                        non_set_color.red   = 0;
-                        non_set_color.green = 0;
-                        non_set_color.blue  = gradient;
-                    }
-                    pixel_buffer.set(i, j, &non_set_color);
+/// Reads the `--fractal`, `--formula`, `--julia`, `--power`, `--polynomial`
+/// and `--relaxation` options.
+fn parse_fractal(options: &HashMap<String, String>) -> Option<Arc<dyn Fractal>> {
+    let registry = Registry::with_builtins();
+    let mut parameters = Parameters::default();
+    if let Some(power) = options.get("power") {
+        parameters.power = Power::from_string(power)?;
+    }
+    if let Some(polynomial) = options.get("polynomial") {
+        parameters.polynomial = Polynomial::from_string(polynomial)?;
+    }
+    if let Some(relaxation) = options.get("relaxation") {
+        parameters.relaxation = constant(relaxation)?;
+    }
+    let fractal: Arc<dyn Fractal> = match options.get("formula") {
+        Some(formula) => Arc::new(Expression::from_string(formula)?),
+        None          => {
+            let name = options.get("fractal").map(|name| name.as_str()).unwrap_or("mandelbrot");
+            match registry.create(name, &parameters) {
+                Some(fractal) => fractal,
+                None          => {
+                    println!("Error : unknown fractal `{}`, it must be one of : {}.", name, registry.names().join(", "));
+                    return None;
                 }
             }
         }
-    });
-
-    quarter_1.join().unwrap();
-    quarter_2.join().unwrap();
-    quarter_3.join().unwrap();
-    quarter_4.join().unwrap();
-    let pixel_buffer = pixel_buffer.lock().unwrap();
-    Image::create_from_pixels(pixel_buffer.width as u32, pixel_buffer.height as u32, pixel_buffer.get()).unwrap()
+    };
+    match options.get("julia") {
+        Some(c) => Some(Arc::new(Julia::new(fractal, complex_from_string(c)?))),
+        None    => Some(fractal),
+    }
 }
 
-fn scale(x: i32, y: i32, image_dim: &ImageDim, plan: &Plan) -> c64 {
-    c64::new(x as f64 * plan.width / image_dim.width as f64 + plan.left, y as f64 * plan.height / image_dim.height as f64 + plan.up)
+/// Reads a Plan, '?' being the default frame of the set.
+fn parse_plan(s: &str, fractal: &Arc<dyn Fractal>) -> Option<Plan> {
+    if s == "?" {
+        Some(fractal.default_plan())
+    } else {
+        Plan::from_string(s)
+    }
 }
 
-fn print_help(category: &str) {
-    println!("");
-    match category {
-        "generate" => {
-            println!("RUST-MANDELBROT : GENERATE");
-            println!("--------------------------\n");
-            println!("Synopsis : generate [plan] [image] [max_iter] [file]\n");
-            println!("  plan     : the frame of the mandelbrot set you want to draw. It must be of the form 'up,left,width,height'. If you want the default settings (that are '-1,-2,3,2'), just type '?'.");
-            println!("  image    : the image size, in pixels. It must match the following pattern : 'width,height'. If you want the default size (that is '900, 600'), type '?'.");
-            println!("  max_iter : the max iterations used to determine the set's points. If you don't know which value you should use, prefer a number around 100.");
-            println!("  file     : the output file to write the image. The format will be guessed from the extension. Supported fromats are : bmp, png, tga and jpg.");
-            println!("--------------------------");
-        },
-        "draw" => {
-            println!("RUST-MANDELBROT : DRAW");
-            println!("----------------------\n");
-            println!("Synopsis : draw [plan] [window] [max_iter]\n");
-            println!("  plan     : the frame of the mandelbrot set you want to use as default view. When you will right-click, it will bring you to this view. It must be of the form 'up,left,width,height'. If you want the default settings (that are '-1,-2,3,2'), just type '?'.");
-            println!("  window   : the size of the window, in pixels. It must match the following pattern : 'width,height'. If you want the default size (that is '900, 600'), type '?'.");
-            println!("  max_iter : the max iterations used to determine the set's points. If you don't know which value you should use, prefer a number around 100.");
-            println!("--------------------------");
+/// Reads the `--depth` option, the bits per channel of the image.
+fn parse_depth(options: &HashMap<String, String>) -> Option<u32> {
+    match options.get("depth").map(|depth| depth.as_str()) {
+        None | Some("8") => Some(8),
+        Some("16")       => Some(16),
+        Some(_)          => {
+            println!("Error : invalid depth, it must be 8 or 16.");
+            None
         }
-        _ => {
-            println!("RUST-MANDELBROT : USE");
-            println!("---------------------\n\nYou must specify a command while calling the programm :");
-            println!("  help      Print this help.");
-            println!("  generate  Generate a Mandelbrot set and save it to an image.");
-            println!("  draw      Launch the interactive drawer.");
-            println!("---------------------\nType `help [command]` to get more specific help about a command.");
-            println!("\nYou don't know about Mandelbrot's set ? Just run with arguments 'draw ? ? 100' :)");
+    }
+}
+
+/// Writes the iteration data in the 'raw' or 'npy' format.
+fn write_data(path: &str, format: &str, plan: &Plan, iterations: &IterationBuffer) {
+    let result = File::create(path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        if format == "raw" {
+            write_raw(&mut writer, plan, iterations)
+        } else {
+            write_npy(&mut writer, iterations)
         }
+    });
+    if let Err(err) = result {
+        exit_with_error(&format!("couldn't write `{}` : {}", path, err));
     }
 }
 
 fn generate_mandelbrot(args: Vec<String>) {
+    let (args, options) = match parse_options(args, &["format", "palette", "coloring", "keep-data", "depth", "fractal", "formula", "julia", "power", "polynomial", "relaxation", "method", "samples", "density"]) {
+        Some(parsed) => parsed,
+        None         => return,
+    };
+    let fractal = match parse_fractal(&options) {
+        Some(fractal) => fractal,
+        None          => return,
+    };
     if args.len() != 6 {
         println!("Error : the `generate` command requires 4 arguments");
         println!("See `help generate` to get specific help");
     } else {
-        let option_plan = Plan::from_string(&args[2]);
+        let option_plan = parse_plan(&args[2], &fractal);
         if let Some(plan) = option_plan {
             let option_image = ImageDim::from_string(&args[3]);
             if let Some(image_dim) = option_image {
                 //Dessin :)
                 if let Ok(max_iter) = args[4].parse() {
-                    let set_color = Color::new_rgb(0, 0, 0);
-                    let mut pixel_buffer = Arc::new(Mutex::new(PixelArrayBuffer::with_size(image_dim.width, image_dim.height)));
-
-                    let half_width = image_dim.width/2;
-                    let half_height = image_dim.height/2;
-
-                    let dim_1 = image_dim.clone();
-                    let plan_1 = plan.clone();
-                    let set_color_1 = set_color;
-                    let buffer_1 = pixel_buffer.clone();
-                    let quarter_1 = thread::spawn(move || {
-                        let mut non_set_color = Color::new_rgb(0, 0, 0);
-                        for i in 0..half_width {
-                            for j in 0..half_height {
-                                let c = scale(i as i32, j as i32, &dim_1, &plan_1);
-                                let mut z = c64::new(0.0, 0.0);
-                                let mut iter = 0;
-                                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                                    iter += 1;
-                                    z = z*z + c;
-                                }
-                                let mut pixel_buffer = buffer_1.lock().unwrap();
-                                if iter == max_iter {
-                                    pixel_buffer.set(i, j, &set_color_1);
-                                } else {
-                                    let ratio = iter as f32/max_iter as f32;
-                                    if ratio > 0.666 {
-                                        let gradient = (255.*ratio) as u8;
-                                        non_set_color.red   = 255;
-                                        non_set_color.green = 255-gradient;
-                                        non_set_color.blue  = 0;
-                                    } else if ratio > 0.333 {
-                                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                                        non_set_color.red   = gradient;
-                                        non_set_color.green = gradient;
-                                        non_set_color.blue  = 255-gradient;
-                                    } else {
-                                        let gradient = (3.*255.*ratio) as u8;
-                                        non_set_color.red   = 0;
-                                        non_set_color.green = 0;
-                                        non_set_color.blue  = gradient;
-                                    }
-                                    pixel_buffer.set(i, j, &non_set_color);
-                                }
-                            }
-                        }
-                    });
-
-                    let dim_2 = image_dim.clone();
-                    let plan_2 = plan.clone();
-                    let set_color_2 = set_color;
-                    let buffer_2 = pixel_buffer.clone();
-                    let quarter_2 = thread::spawn(move || {
-                        let mut non_set_color = Color::new_rgb(0, 0, 0);
-                        for i in half_width..dim_2.width {
-                            for j in 0..half_height {
-                                let c = scale(i as i32, j as i32, &dim_2, &plan_2);
-                                let mut z = c64::new(0.0, 0.0);
-                                let mut iter = 0;
-                                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                                    iter += 1;
-                                    z = z*z + c;
-                                }
-                                let mut pixel_buffer = buffer_2.lock().unwrap();
-                                if iter == max_iter {
-                                    pixel_buffer.set(i, j, &set_color_2);
-                                } else {
-                                    let ratio = iter as f32/max_iter as f32;
-                                    if ratio > 0.666 {
-                                        let gradient = (255.*ratio) as u8;
-                                        non_set_color.red   = 255;
-                                        non_set_color.green = 255-gradient;
-                                        non_set_color.blue  = 0;
-                                    } else if ratio > 0.333 {
-                                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                                        non_set_color.red   = gradient;
-                                        non_set_color.green = gradient;
-                                        non_set_color.blue  = 255-gradient;
-                                    } else {
-                                        let gradient = (3.*255.*ratio) as u8;
-                                        non_set_color.red   = 0;
-                                        non_set_color.green = 0;
-                                        non_set_color.blue  = gradient;
-                                    }
-                                    pixel_buffer.set(i, j, &non_set_color);
-                                }
-                            }
-                        }
-                    });
-
-                    let dim_3 = image_dim.clone();
-                    let plan_3 = plan.clone();
-                    let set_color_3 = set_color;
-                    let buffer_3 = pixel_buffer.clone();
-                    let quarter_3 = thread::spawn(move || {
-                        let mut non_set_color = Color::new_rgb(0, 0, 0);
-                        for i in 0..half_width {
-                            for j in half_height..dim_3.height {
-                                let c = scale(i as i32, j as i32, &dim_3, &plan_3);
-                                let mut z = c64::new(0.0, 0.0);
-                                let mut iter = 0;
-                                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                                    iter += 1;
-                                    z = z*z + c;
-                                }
-                                let mut pixel_buffer = buffer_3.lock().unwrap();
-                                if iter == max_iter {
-                                    pixel_buffer.set(i, j, &set_color_3);
-                                } else {
-                                    let ratio = iter as f32/max_iter as f32;
-                                    if ratio > 0.666 {
-                                        let gradient = (255.*ratio) as u8;
-                                        non_set_color.red   = 255;
-                                        non_set_color.green = 255-gradient;
-                                        non_set_color.blue  = 0;
-                                    } else if ratio > 0.333 {
-                                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                                        non_set_color.red   = gradient;
-                                        non_set_color.green = gradient;
-                                        non_set_color.blue  = 255-gradient;
-                                    } else {
-                                        let gradient = (3.*255.*ratio) as u8;
-                                        non_set_color.red   = 0;
-                                        non_set_color.green = 0;
-                                        non_set_color.blue  = gradient;
-                                    }
-                                    pixel_buffer.set(i, j, &non_set_color);
-                                }
-                            }
-                        }
-                    });
-
-                    let dim_4 = image_dim.clone();
-                    let plan_4 = plan.clone();
-                    let set_color_4 = set_color;
-                    let buffer_4 = pixel_buffer.clone();
-                    let quarter_4 = thread::spawn(move || {
-                        let mut non_set_color = Color::new_rgb(0, 0, 0);
-                        for i in half_width..dim_4.width {
-                            for j in half_height..dim_4.height {
-                                let c = scale(i as i32, j as i32, &dim_4, &plan_4);
-                                let mut z = c64::new(0.0, 0.0);
-                                let mut iter = 0;
-                                while z.re()*z.re()+z.im()*z.im() < 4.0 && iter < max_iter {
-                                    iter += 1;
-                                    z = z*z + c;
-                                }
-                                let mut pixel_buffer = buffer_4.lock().unwrap();
-                                if iter == max_iter {
-                                    pixel_buffer.set(i, j, &set_color_4);
-                                } else {
-                                    let ratio = iter as f32/max_iter as f32;
-                                    if ratio > 0.666 {
-                                        let gradient = (255.*ratio) as u8;
-                                        non_set_color.red   = 255;
-                                        non_set_color.green = 255-gradient;
-                                        non_set_color.blue  = 0;
-                                    } else if ratio > 0.333 {
-                                        let gradient = (3.*255.*(ratio-0.333)) as u8;
-                                        non_set_color.red   = gradient;
-                                        non_set_color.green = gradient;
-                                        non_set_color.blue  = 255-gradient;
-                                    } else {
-                                        let gradient = (3.*255.*ratio) as u8;
-                                        non_set_color.red   = 0;
-                                        non_set_color.green = 0;
-                                        non_set_color.blue  = gradient;
-                                    }
-                                    pixel_buffer.set(i, j, &non_set_color);
-                                }
-                            }
+                    let (palette, coloring) = match parse_coloring(&options) {
+                        Some(parsed) => parsed,
+                        None         => return,
+                    };
+                    let depth = match parse_depth(&options) {
+                        Some(depth) => depth,
+                        None        => return,
+                    };
+                    let path = &(*args[5]);
+                    let format = match options.get("format") {
+                        Some(format) => format.clone(),
+                        None if path.ends_with(".raw") => "raw".to_string(),
+                        None if path.ends_with(".npy") => "npy".to_string(),
+                        None         => "image".to_string(),
+                    };
+                    if format != "image" && format != "raw" && format != "npy" {
+                        println!("Error : unknown format `{}`, it must be 'image', 'raw' or 'npy'.", format);
+                        return;
+                    }
+                    let method = match options.get("method") {
+                        Some(method) => match Method::from_string(method) {
+                            Some(method) => method,
+                            None         => return,
+                        },
+                        None         => Method::EscapeTime,
+                    };
+                    if method != Method::EscapeTime {
+                        if format != "image" {
+                            println!("Error : the inverse iteration methods only write images.");
+                            return;
                         }
-                    });
-
-                    quarter_1.join().unwrap();
-                    quarter_2.join().unwrap();
-                    quarter_3.join().unwrap();
-                    quarter_4.join().unwrap();
-                    let pixel_buffer = pixel_buffer.lock().unwrap();
-                    let img = Image::create_from_pixels(pixel_buffer.width as u32, pixel_buffer.height as u32, pixel_buffer.get()).unwrap();
-                    img.save_to_file(&(*args[5]));
+                        generate_preimages(&fractal, method, &options, &plan, &image_dim, max_iter, depth, path);
+                        return;
+                    }
+                    let iterations = compute_iterations(&fractal, &plan, &image_dim, max_iter, DEFAULT_THREADS);
+                    if let Some(data_path) = options.get("keep-data") {
+                        write_data(data_path, "raw", &plan, &iterations);
+                    }
+                    if format == "image" {
+                        save_colored(&iterations, &palette, coloring, depth, path);
+                    } else {
+                        write_data(path, &format, &plan, &iterations);
+                    }
                 }
             }
         }
     }
 }
 
-fn draw_main(args: Vec<String>) {
-    if args.len() != 5 {
-        println!("");
-        println!("Error : the `draw` command requires 3 arguments");
-        println!("See `help draw` to get specific help");
+/// Draws a Julia set with the inverse iteration methods, white points on
+/// black.
+#[allow(clippy::too_many_arguments)]
+fn generate_preimages(fractal: &Arc<dyn Fractal>, method: Method, options: &HashMap<String, String>, plan: &Plan, image_dim: &ImageDim, max_iter: u32, depth: u32, path: &str) {
+    let c = match options.get("julia") {
+        Some(c) => match complex_from_string(c) {
+            Some(c) => c,
+            None    => return,
+        },
+        None    => {
+            println!("Error : the inverse iteration methods draw Julia sets, they need `--julia`.");
+            return;
+        }
+    };
+    if fractal.preimages(c, c).is_empty() {
+        println!("Error : the inverse iteration methods need the 'mandelbrot' or 'tricorn' fractal with an integer power.");
+        return;
+    }
+    //the random walk visits some pixels far more than others, the log and the
+    //median as white keep the rarely visited ones visible
+    let (density, tone, clip) = if method == Method::Inverse {
+        let points = match options.get("samples").map(|samples| samples.parse()) {
+            None              => 10*(image_dim.width*image_dim.height) as u64,
+            Some(Ok(samples)) => samples,
+            Some(Err(_))      => {
+                println!("Error : invalid sample count.");
+                return;
+            }
+        };
+        (inverse_iteration(&**fractal, c, plan, image_dim, points, 0), ToneMap::Log, 50.)
     } else {
-        let option_plan = Plan::from_string(&args[2]);
-        if let Some(mut plan) = option_plan {
-            let option_image = ImageDim::from_string(&args[3]);
-            if let Some(mut image_dim) = option_image {
-                //Dessin :)
-                if let Ok(mut max_iter) = args[4].parse::<u32>() {
-                    let mut window = RenderWindow::new(VideoMode::new_init(image_dim.width as u32, image_dim.height as u32, 32),
-                                                   "Mandelbrot",
-                                                   WindowStyle::Close,
-                                                   &ContextSettings::default()).expect("Couldn't create RenderWindow");
-                    window.set_framerate_limit(30);
-
-                    let mut redraw = false; //should we redraw the set ?
-                    let mut img = draw_mandelbrot(&Color::new_rgb(0, 0, 0),
-                                    &plan,
-                                    &image_dim,
-                                    max_iter);
-                    let mut tex = Texture::new_from_image(&img).unwrap();
-
-                    let mut zoom_lvl = 0;
-                    let mut rect = RectangleShape::new_init(&Vector2f::new(90., 60.)).unwrap();
-                    rect.set_origin(&Vector2f::new(45., 30.));
-                    rect.set_fill_color(&Color::new_rgba(0, 0, 0, 0));
-                    rect.set_outline_color(&Color::new_rgb(255, 255, 255));
-                    rect.set_outline_thickness(1.);
-
-                    while window.is_open() {
-                        for event in window.events() {
-                            match event {
-                                event::Closed => window.close(),
-                                event::MouseButtonPressed {
-                                    button, x, y
-                                } => {
-                                        if button == MouseButton::MouseRight {
-                                            zoom_lvl = 0;
-                                            redraw = true;
-                                            plan = Plan {up: -1.0, left: -2.0, width: 3.0, height: 2.0}; //valeurs par défaut
-                                        } else if button == MouseButton::MouseLeft {
-                                            redraw = true;
-                                            //zoom sur un rectangle de 90*60 centré sur la souris
-                                            let left = x - 45;
-                                            let up = y - 30;
-                                            let up_left = scale(left, up, &image_dim, &plan);
-                                            let width_height = scale(90, 60, &image_dim, &Plan {up: 0., left: 0., width: plan.width, height: plan.height});
-                                            plan = Plan {up: up_left.im(), left: up_left.re(), width: width_height.re(), height: width_height.im()};
-                                            zoom_lvl += 1;
-                                            println!("ZOOM: {} sur ({:.5}; {:.5})", zoom_lvl, plan.left+plan.width/2., plan.up+plan.height/2.);
-                                        }
-                                    },
-                                event::MouseMoved {
-                                    x, y
-                                } => rect.set_position(&Vector2f::new(x as f32, y as f32)),
-                                _ => {},
-                            }
-                        }
+        let limit = match options.get("density").map(|density| density.parse()) {
+            None                             => 1,
+            Some(Ok(density)) if density > 0 => density,
+            Some(_)                          => {
+                println!("Error : invalid density, it must be a positive integer.");
+                return;
+            }
+        };
+        (modified_inverse_iteration(&**fractal, c, plan, image_dim, max_iter, limit), ToneMap::Linear, 100.)
+    };
+    let pixel_buffer = tone_map(&[density], tone, clip);
+    save_float_pixels(&pixel_buffer, depth, path);
+}
 
-                        if redraw {
-                            img = draw_mandelbrot(&Color::new_rgb(0, 0, 0),
-                                            &plan,
-                                            &image_dim,
-                                            max_iter*(zoom_lvl+1));
-                            tex = Texture::new_from_image(&img).unwrap();
-                            redraw = false;
-                        }
+fn buddhabrot_main(args: Vec<String>) {
+    let (args, options) = match parse_options(args, &["samples", "seed", "threads", "tone", "clip", "depth", "fractal", "formula", "julia", "power"]) {
+        Some(parsed) => parsed,
+        None         => return,
+    };
+    if args.len() != 6 {
+        println!("Error : the `buddhabrot` command requires 4 arguments");
+        println!("See `help buddhabrot` to get specific help");
+        return;
+    }
+    let fractal = match parse_fractal(&options) {
+        Some(fractal) => fractal,
+        None          => return,
+    };
+    if fractal.converges() {
+        println!("Error : the Buddhabrot needs a fractal whose points escape.");
+        return;
+    }
+    let plan = match parse_plan(&args[2], &fractal) {
+        Some(plan) => plan,
+        None       => return,
+    };
+    let image_dim = match ImageDim::from_string(&args[3]) {
+        Some(image_dim) => image_dim,
+        None            => return,
+    };
+    //"1000" for the Buddhabrot, "5000,500,50" for a red, green and blue Nebulabrot
+    let limits = match args[4].split(',').map(|limit| limit.parse()).collect::<Result<Vec<u32>, _>>() {
+        Ok(limits) if limits.len() == 1 || limits.len() == 3 => limits,
+        _ => {
+            println!("Error : invalid max_iter, it must be a number or three numbers 'red,green,blue'.");
+            return;
+        }
+    };
+    let samples = match options.get("samples").map(|samples| samples.parse()) {
+        None              => 10*(image_dim.width*image_dim.height) as u64,
+        Some(Ok(samples)) => samples,
+        Some(Err(_))      => {
+            println!("Error : invalid sample count.");
+            return;
+        }
+    };
+    let seed = match options.get("seed").map(|seed| seed.parse()) {
+        None           => SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0),
+        Some(Ok(seed)) => seed,
+        Some(Err(_))   => {
+            println!("Error : invalid seed, it must be a positive integer.");
+            return;
+        }
+    };
+    let threads = match options.get("threads").map(|threads| threads.parse()) {
+        None                             => DEFAULT_THREADS,
+        Some(Ok(threads)) if threads > 0 => threads,
+        Some(_)                          => {
+            println!("Error : invalid thread count.");
+            return;
+        }
+    };
+    let tone = match options.get("tone") {
+        Some(tone) => match ToneMap::from_string(tone) {
+            Some(tone) => tone,
+            None       => return,
+        },
+        None       => ToneMap::Linear,
+    };
+    let clip = match options.get("clip").map(|clip| clip.parse::<f32>()) {
+        None                                           => 99.9,
+        Some(Ok(clip)) if (0. ..=100.).contains(&clip) => clip,
+        Some(_)                                        => {
+            println!("Error : invalid clip, it must be a percentile between 0 and 100.");
+            return;
+        }
+    };
+    let depth = match parse_depth(&options) {
+        Some(depth) => depth,
+        None        => return,
+    };
+
+    println!("BUDDHABROT: {} samples, seed {}", samples, seed);
+    let densities = compute_density(&fractal, &plan, &image_dim, &limits, samples, seed, threads);
+    let pixel_buffer = tone_map(&densities, tone, clip);
+    let path = &args[5];
+    save_float_pixels(&pixel_buffer, depth, path);
+}
 
-                        window.clear(&Color::black());
-                        window.draw(&Sprite::new_with_texture(&tex).unwrap());
-                        window.draw(&rect);
-                        window.display();
-                    }
-                }
-            }
+fn colorize_main(args: Vec<String>) {
+    let (args, options) = match parse_options(args, &["palette", "coloring", "depth"]) {
+        Some(parsed) => parsed,
+        None         => return,
+    };
+    if args.len() != 4 {
+        println!("Error : the `colorize` command requires 2 arguments");
+        println!("See `help colorize` to get specific help");
+    } else {
+        let (palette, coloring) = match parse_coloring(&options) {
+            Some(parsed) => parsed,
+            None         => return,
+        };
+        let depth = match parse_depth(&options) {
+            Some(depth) => depth,
+            None        => return,
+        };
+        let data = File::open(&args[2]).and_then(|file| read_raw(&mut BufReader::new(file)));
+        match data {
+            Ok((_, iterations)) => save_colored(&iterations, &palette, coloring, depth, &args[3]),
+            Err(err)            => exit_with_error(&format!("couldn't read `{}` : {}", args[2], err)),
         }
     }
 }
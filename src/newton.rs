@@ -0,0 +1,226 @@
+//! Newton's method fractals and their Nova relatives, whose points converge
+//! to an attractor instead of escaping.
+
+use complex::*;
+use plan::Plan;
+use fractal::Fractal;
+use expr;
+use cmath;
+
+/// A polynomial with complex coefficients, highest degree first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    coefficients: Vec<c64>,
+}
+
+impl Default for Polynomial {
+    /// `z^3 - 1`.
+    fn default() -> Polynomial {
+        Polynomial::new(vec![c64::new(1., 0.), c64::new(0., 0.), c64::new(0., 0.), c64::new(-1., 0.)])
+    }
+}
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<c64>) -> Polynomial {
+        let first = coefficients.iter().position(|a| a.re() != 0. || a.im() != 0.).unwrap_or(coefficients.len());
+        Polynomial {
+            coefficients: coefficients[first..].to_vec(),
+        }
+    }
+
+    //"1,0,0,-1" for z^3 - 1, each coefficient being a constant expression like "2*i"
+    pub fn from_string(s: &str) -> Option<Polynomial> {
+        let mut coefficients = Vec::new();
+        for coefficient in s.split(',') {
+            coefficients.push(expr::constant(coefficient)?);
+        }
+        let polynomial = Polynomial::new(coefficients);
+        if polynomial.degree() < 2 || polynomial.degree() > 254 {
+            println!("Error : invalid polynomial, its degree must be between 2 and 254.");
+            return None;
+        }
+        Some(polynomial)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Value at `z`, by Horner's method.
+    pub fn eval(&self, z: c64) -> c64 {
+        self.coefficients.iter().fold(c64::new(0., 0.), |value, &a| value*z + a)
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        let degree = self.degree();
+        Polynomial::new(self.coefficients.iter().take(degree).enumerate()
+                            .map(|(k, &a)| a*((degree - k) as f64))
+                            .collect())
+    }
+
+    /// All the roots, with the Durand-Kerner method.
+    pub fn roots(&self) -> Vec<c64> {
+        let degree = self.degree();
+        if degree == 0 {
+            return Vec::new();
+        }
+        let leading = self.coefficients[0];
+        let monic = Polynomial::new(self.coefficients.iter().map(|&a| a/leading).collect());
+        let seed = c64::new(0.4, 0.9);
+        let mut roots: Vec<c64> = (0..degree).map(|k| cmath::powi(seed, k as u32)).collect();
+        for _ in 0..500 {
+            let mut moved = 0.;
+            for k in 0..degree {
+                let mut denominator = c64::new(1., 0.);
+                for (l, &other) in roots.iter().enumerate() {
+                    if l != k {
+                        denominator = denominator*(roots[k] - other);
+                    }
+                }
+                let delta = monic.eval(roots[k])/denominator;
+                roots[k] = roots[k] - delta;
+                moved += cmath::norm_sqr(delta);
+            }
+            if moved < 1e-28 {
+                break;
+            }
+        }
+        roots
+    }
+}
+
+/// Index of the root `z` converged to, if it is close to one.
+fn nearest_root(roots: &[c64], z: c64) -> Option<u8> {
+    roots.iter()
+        .position(|&root| cmath::norm_sqr(z - root) < 1e-6)
+        .map(|index| index as u8)
+}
+
+fn bounds(roots: &[c64]) -> Plan {
+    let count = roots.len().max(1) as f64;
+    let center = roots.iter().fold(c64::new(0., 0.), |sum, &root| sum + root)/count;
+    let radius = roots.iter().map(|&root| (root - center).abs()).fold(0.5, f64::max);
+    Plan {
+        up: center.im() - 1.5*radius,
+        left: center.re() - 2.25*radius,
+        width: 4.5*radius,
+        height: 3.*radius,
+    }
+}
+
+/// `z = z - a*p(z)/p'(z)`, `z` starting at the pixel : every pixel is
+/// colored by the root of `p` it converges to.
+#[derive(Debug, Clone)]
+pub struct Newton {
+    polynomial: Polynomial,
+    derivative: Polynomial,
+    roots: Vec<c64>,
+    relaxation: c64,
+}
+
+impl Newton {
+    pub fn new(polynomial: Polynomial, relaxation: c64) -> Newton {
+        Newton {
+            derivative: polynomial.derivative(),
+            roots: polynomial.roots(),
+            polynomial,
+            relaxation,
+        }
+    }
+}
+
+impl Fractal for Newton {
+    fn name(&self) -> &str {
+        "newton"
+    }
+
+    fn init(&self, pixel: c64) -> (c64, c64) {
+        (pixel, c64::new(0., 0.))
+    }
+
+    fn step(&self, z: c64, c: c64, _pixel: c64) -> c64 {
+        z - self.relaxation*self.polynomial.eval(z)/self.derivative.eval(z) + c
+    }
+
+    fn escaped(&self, z: c64) -> bool {
+        cmath::norm_sqr(z) >= 1e20
+    }
+
+    fn converges(&self) -> bool {
+        true
+    }
+
+    fn root(&self, z: c64) -> Option<u8> {
+        nearest_root(&self.roots, z)
+    }
+
+    fn default_plan(&self) -> Plan {
+        bounds(&self.roots)
+    }
+
+    fn julia_plan(&self) -> Option<Plan> {
+        Some(bounds(&self.roots))
+    }
+}
+
+/// `z = z - a*p(z)/p'(z) + c`, `z` starting at a root of `p` and `c` being
+/// the pixel.
+#[derive(Debug, Clone)]
+pub struct Nova {
+    newton: Newton,
+    start: c64,
+}
+
+impl Nova {
+    pub fn new(polynomial: Polynomial, relaxation: c64) -> Nova {
+        let newton = Newton::new(polynomial, relaxation);
+        //the root with the largest real part, 1 for z^3 - 1
+        let start = newton.roots.iter().cloned()
+            .fold(None, |best: Option<c64>, root| match best {
+                Some(best) if best.re() >= root.re() => Some(best),
+                _                                    => Some(root),
+            })
+            .unwrap_or(c64::new(1., 0.));
+        Nova {
+            newton,
+            start,
+        }
+    }
+}
+
+impl Fractal for Nova {
+    fn name(&self) -> &str {
+        "nova"
+    }
+
+    fn init(&self, pixel: c64) -> (c64, c64) {
+        (self.start, pixel)
+    }
+
+    fn step(&self, z: c64, c: c64, pixel: c64) -> c64 {
+        self.newton.step(z, c, pixel)
+    }
+
+    fn escaped(&self, z: c64) -> bool {
+        self.newton.escaped(z)
+    }
+
+    fn converges(&self) -> bool {
+        true
+    }
+
+    /// Far from 0 the orbits converge slowly everywhere, the details are
+    /// around the origin.
+    fn default_plan(&self) -> Plan {
+        Plan {
+            up: -1.2,
+            left: -2.05,
+            width: 3.6,
+            height: 2.4,
+        }
+    }
+
+    fn julia_plan(&self) -> Option<Plan> {
+        self.newton.julia_plan()
+    }
+}
@@ -0,0 +1,328 @@
+use std::cmp;
+use buffer::{Color, IterationBuffer, PixelArrayBuffer, FloatPixelBuffer};
+
+/// A gradient going through evenly spaced color stops. A cyclic one blends
+/// the last stop back into the first one.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    stops: Vec<Color>,
+    cyclic: bool,
+}
+
+impl Palette {
+    pub fn new(stops: Vec<Color>) -> Palette {
+        assert!(!stops.is_empty());
+        Palette {
+            stops,
+            cyclic: true,
+        }
+    }
+
+    /// A gradient going from the first stop to the last one, without
+    /// blending back.
+    pub fn gradient(stops: Vec<Color>) -> Palette {
+        Palette {
+            cyclic: false,
+            ..Palette::new(stops)
+        }
+    }
+
+    /// The colors of the default palette, red blending back into black so
+    /// that it cycles without a seam.
+    pub fn cyclic() -> Palette {
+        Palette::new(vec![
+            Color::new_rgb(0, 0, 0),
+            Color::new_rgb(0, 0, 255),
+            Color::new_rgb(255, 255, 0),
+            Color::new_rgb(255, 0, 0),
+        ])
+    }
+
+    /// Grey levels, from black to white.
+    pub fn grayscale() -> Palette {
+        Palette::new(vec![
+            Color::new_rgb(0, 0, 0),
+            Color::new_rgb(255, 255, 255),
+        ])
+    }
+
+    pub fn fire() -> Palette {
+        Palette::new(vec![
+            Color::new_rgb(0, 0, 0),
+            Color::new_rgb(128, 0, 0),
+            Color::new_rgb(255, 96, 0),
+            Color::new_rgb(255, 224, 64),
+            Color::new_rgb(255, 255, 255),
+        ])
+    }
+
+    pub fn ocean() -> Palette {
+        Palette::new(vec![
+            Color::new_rgb(0, 7, 100),
+            Color::new_rgb(32, 107, 203),
+            Color::new_rgb(237, 255, 255),
+            Color::new_rgb(255, 170, 0),
+            Color::new_rgb(0, 2, 0),
+        ])
+    }
+
+    /// Either the name of a built-in palette ('default', 'cyclic',
+    /// 'grayscale', 'fire' or 'ocean') or a list of colors like '000000,ff8800,ffffff'.
+    pub fn from_string(s: &str) -> Option<Palette> {
+        match s {
+            "default"   => return Some(Palette::default()),
+            "cyclic"    => return Some(Palette::cyclic()),
+            "grayscale" => return Some(Palette::grayscale()),
+            "fire"      => return Some(Palette::fire()),
+            "ocean"     => return Some(Palette::ocean()),
+            _           => {},
+        }
+        let mut stops = Vec::new();
+        for stop in s.split(',') {
+            let stop = stop.trim_start_matches('#');
+            let rgb = if stop.len() == 6 { u32::from_str_radix(stop, 16).ok() } else { None };
+            match rgb {
+                Some(rgb) => stops.push(Color::new_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+                None      => {
+                    println!("Error : invalid palette, it must be a palette name or a list of colors like '000000,ff8800'.");
+                    return None;
+                }
+            }
+        }
+        Some(Palette::new(stops))
+    }
+
+    /// The two stops around position `t` and where `t` is between them.
+    fn segment(&self, t: f32) -> (&Color, &Color, f32) {
+        let spans = if self.cyclic { self.stops.len() } else { self.stops.len() - 1 };
+        let position = (t - t.floor())*spans as f32;
+        let index = (position as usize) % self.stops.len();
+        let ratio = position - position.floor();
+        (&self.stops[index], &self.stops[(index + 1) % self.stops.len()], ratio)
+    }
+
+    /// Color at position `t`. Only the fractional part of `t` is used, so
+    /// the palette can be shifted by any offset.
+    pub fn color_at(&self, t: f32) -> Color {
+        let (from, to, ratio) = self.segment(t);
+        Color::new_rgb(
+            lerp(from.red, to.red, ratio),
+            lerp(from.green, to.green, ratio),
+            lerp(from.blue, to.blue, ratio)
+        )
+    }
+
+    /// Same as `color_at`, with components between 0 and 1 that aren't
+    /// rounded to 8 bits.
+    pub fn color_at_f32(&self, t: f32) -> [f32; 3] {
+        let (from, to, ratio) = self.segment(t);
+        let from = to_f32(from);
+        let to = to_f32(to);
+        [
+            from[0] + (to[0] - from[0])*ratio,
+            from[1] + (to[1] - from[1])*ratio,
+            from[2] + (to[2] - from[2])*ratio,
+        ]
+    }
+}
+
+impl Default for Palette {
+    /// The original gradient : black to blue up to 1/3, blue to yellow up to
+    /// 2/3, then red.
+    fn default() -> Palette {
+        Palette::gradient(vec![
+            Color::new_rgb(0, 0, 0),
+            Color::new_rgb(0, 0, 255),
+            Color::new_rgb(255, 255, 0),
+            Color::new_rgb(255, 0, 0),
+        ])
+    }
+}
+
+fn lerp(from: u8, to: u8, ratio: f32) -> u8 {
+    (from as f32 + (to as f32 - from as f32)*ratio) as u8
+}
+
+fn to_f32(color: &Color) -> [f32; 3] {
+    [color.red as f32/255., color.green as f32/255., color.blue as f32/255.]
+}
+
+/// How the iteration data of a pixel is turned into a palette position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coloring {
+    /// Escape count divided by max_iter.
+    Iterations,
+    /// Smooth iteration count divided by max_iter, without the bands.
+    Smooth,
+    /// Escape counts spread evenly over the palette (histogram equalization).
+    Histogram,
+    /// One color per root reached, darker as more iterations were needed,
+    /// for the Newton fractals. Pixels without a root are colored smoothly.
+    Roots,
+}
+
+impl Coloring {
+    pub fn from_string(s: &str) -> Option<Coloring> {
+        match s {
+            "iterations" => Some(Coloring::Iterations),
+            "smooth"     => Some(Coloring::Smooth),
+            "histogram"  => Some(Coloring::Histogram),
+            "roots"      => Some(Coloring::Roots),
+            _            => {
+                println!("Error : invalid coloring mode, it must be 'iterations', 'smooth', 'histogram' or 'roots'.");
+                None
+            }
+        }
+    }
+}
+
+/// Turns the iteration data of a pixel into a palette position.
+struct Positions {
+    coloring: Coloring,
+    max_iter: u32,
+    //cumulative distribution of the escape counts, for the histogram coloring
+    cumulative: Vec<u64>,
+    escaped: u64,
+    //number of roots, for the roots coloring
+    roots: u32,
+}
+
+impl Positions {
+    fn new(iterations: &IterationBuffer, coloring: Coloring) -> Positions {
+        let max_iter = iterations.max_iter;
+        let mut cumulative = Vec::new();
+        if coloring == Coloring::Histogram {
+            //up to the highest escape count, max_iter may be much higher
+            let highest = iterations.get().iter().cloned().filter(|&iter| iter < max_iter).max().unwrap_or(0);
+            cumulative = vec![0u64; highest as usize + 1];
+            for &iter in iterations.get() {
+                if iter < max_iter {
+                    cumulative[iter as usize] += 1;
+                }
+            }
+            for iter in 1..cumulative.len() {
+                cumulative[iter] += cumulative[iter - 1];
+            }
+        }
+        let escaped = cumulative.last().cloned().unwrap_or(0).max(1);
+        let roots = iterations.get_roots().iter().cloned().max().unwrap_or(0) as u32;
+        Positions {
+            coloring,
+            max_iter,
+            cumulative,
+            escaped,
+            roots,
+        }
+    }
+
+    /// Position of the pixel in the palette and the brightness of its color,
+    /// or None if it is in the set.
+    fn at(&self, iterations: &IterationBuffer, i: usize, j: usize) -> Option<(f32, f32)> {
+        let iter = iterations.at(i, j);
+        if iter >= self.max_iter {
+            return None;
+        }
+        Some(match (self.coloring, iterations.root_at(i, j)) {
+            (Coloring::Iterations, _)    => (iter as f32/self.max_iter as f32, 1.),
+            (Coloring::Smooth, _)        => (iterations.smooth_at(i, j)/self.max_iter as f32, 1.),
+            (Coloring::Histogram, _)     => {
+                let below = if iter == 0 { 0 } else { self.cumulative[cmp::min(iter as usize, self.cumulative.len()) - 1] };
+                (below as f32/self.escaped as f32, 1.)
+            },
+            (Coloring::Roots, Some(root)) => {
+                //the roots are spread inside the palette, away from its first stop
+                let shade = 1. - iterations.smooth_at(i, j)/self.max_iter as f32;
+                ((root as f32 + 1.)/(self.roots as f32 + 1.), shade*shade)
+            },
+            (Coloring::Roots, None)       => (iterations.smooth_at(i, j)/self.max_iter as f32, 1.),
+        })
+    }
+}
+
+fn shaded(color: Color, shade: f32) -> Color {
+    Color::new_rgb(
+        (color.red as f32*shade) as u8,
+        (color.green as f32*shade) as u8,
+        (color.blue as f32*shade) as u8
+    )
+}
+
+/// Colors an iteration buffer. `offset` shifts the palette, which is what
+/// the viewer uses to cycle the colors.
+pub fn colorize(iterations: &IterationBuffer, palette: &Palette, coloring: Coloring, set_color: &Color, offset: f32) -> PixelArrayBuffer {
+    let positions = Positions::new(iterations, coloring);
+    let mut pixel_buffer = PixelArrayBuffer::with_size(iterations.width, iterations.height);
+    for j in 0..iterations.height {
+        for i in 0..iterations.width {
+            match positions.at(iterations, i, j) {
+                Some((position, shade)) => pixel_buffer.set(i, j, &shaded(palette.color_at(position + offset), shade)),
+                None                    => pixel_buffer.set(i, j, set_color),
+            }
+        }
+    }
+    pixel_buffer
+}
+
+/// Same as `colorize`, keeping the colors as floats for the 16 bits and HDR
+/// outputs.
+pub fn colorize_f32(iterations: &IterationBuffer, palette: &Palette, coloring: Coloring, set_color: &Color, offset: f32) -> FloatPixelBuffer {
+    let positions = Positions::new(iterations, coloring);
+    let set_color = to_f32(set_color);
+    let mut pixel_buffer = FloatPixelBuffer::with_size(iterations.width, iterations.height);
+    for j in 0..iterations.height {
+        for i in 0..iterations.width {
+            match positions.at(iterations, i, j) {
+                Some((position, shade)) => {
+                    let color = palette.color_at_f32(position + offset);
+                    pixel_buffer.set(i, j, [color[0]*shade, color[1]*shade, color[2]*shade]);
+                },
+                None => pixel_buffer.set(i, j, set_color),
+            }
+        }
+    }
+    pixel_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_palette_keeps_the_original_gradient() {
+        let palette = Palette::default();
+        assert_eq!(palette.color_at(0.), Color::new_rgb(0, 0, 0));
+        assert_eq!(palette.color_at(1./6.), Color::new_rgb(0, 0, 127));
+        assert_eq!(palette.color_at(1./3.), Color::new_rgb(0, 0, 255));
+        assert_eq!(palette.color_at(2./3.), Color::new_rgb(255, 255, 0));
+        assert_eq!(palette.color_at(0.999).red, 255);
+        assert!(palette.color_at(0.999).green < 5);
+    }
+
+    #[test]
+    fn cyclic_palette_blends_back_into_the_first_stop() {
+        let palette = Palette::cyclic();
+        assert_eq!(palette.color_at(0.25), Color::new_rgb(0, 0, 255));
+        assert_eq!(palette.color_at(0.75), Color::new_rgb(255, 0, 0));
+        assert!(palette.color_at(0.999).red < 5);
+    }
+
+    #[test]
+    fn histogram_spreads_the_escape_counts() {
+        let iterations = IterationBuffer::from_vec(vec![0, 1, 1, 3, 10, 10], vec![0.; 6], 3, 2, 10);
+        let positions = Positions::new(&iterations, Coloring::Histogram);
+        assert_eq!(positions.cumulative.len(), 4);
+        assert_eq!(positions.at(&iterations, 0, 0), Some((0., 1.)));
+        assert_eq!(positions.at(&iterations, 1, 0), Some((0.25, 1.)));
+        assert_eq!(positions.at(&iterations, 0, 1), Some((0.75, 1.)));
+        assert_eq!(positions.at(&iterations, 1, 1), None);
+    }
+
+    #[test]
+    fn counts_above_max_iter_are_in_the_set() {
+        let iterations = IterationBuffer::from_vec(vec![2, 499], vec![0.; 2], 2, 1, 10);
+        for &coloring in &[Coloring::Iterations, Coloring::Histogram] {
+            let positions = Positions::new(&iterations, coloring);
+            assert_eq!(positions.at(&iterations, 1, 0), None);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use complex::*;
+
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub up: f64,
+    pub left: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Plan {
+    //"2,1,3,4"
+    pub fn from_string(s: &str) -> Option<Plan> {
+        if s == "?" {
+            return Some(Plan::default());
+        }
+        match Plan::parse(s) {
+            Ok(plan)     => Some(plan),
+            Err(message) => {
+                println!("Error : invalid Plan format, {}.", message);
+                None
+            }
+        }
+    }
+
+    /// Reads `up,left,width,height` without printing anything nor accepting
+    /// `?`, for the files the plans are saved in.
+    pub fn parse(s: &str) -> Result<Plan, &'static str> {
+        let coords: Vec<Option<f64>> = s.split(',').map(|coord| coord.trim().parse().ok()).collect();
+        if coords.len() != 4 {
+            return Err("it must match 'x,y,w,h'");
+        }
+        match (coords[0], coords[1], coords[2], coords[3]) {
+            (Some(up), Some(left), Some(width), Some(height)) => Ok(Plan {up, left, width, height}),
+            _                                                 => Err("only numbers are accepted"),
+        }
+    }
+
+    /// Zooms in by `factor` (out if it is below 1) around `point`, which
+    /// stays at the same place in the frame.
+    pub fn zoom_at(&self, point: c64, factor: f64) -> Plan {
+        Plan {
+            up: point.im() - (point.im() - self.up)/factor,
+            left: point.re() - (point.re() - self.left)/factor,
+            width: self.width/factor,
+            height: self.height/factor,
+        }
+    }
+}
+
+impl Default for Plan {
+    /// The whole Mandelbrot set.
+    fn default() -> Plan {
+        Plan {
+            up: -1.,
+            left: -2.,
+            width: 3.,
+            height: 2.,
+        }
+    }
+}
+
+//"-0.8,0.156"
+pub fn complex_from_string(s: &str) -> Option<c64> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 2 {
+        println!("Error : invalid complex number format, it must match 're,im'.");
+        return None;
+    }
+    match (parts[0].parse(), parts[1].parse()) {
+        (Ok(re), Ok(im)) => Some(c64::new(re, im)),
+        _                => {
+            println!("Error : invalid complex number format, only numbers are accepted.");
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageDim {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ImageDim {
+    pub fn from_string(s: &str) -> Option<ImageDim> {
+        if s == "?" {
+            return Some(ImageDim {
+                width: 900,
+                height: 600,
+            });
+        }
+        let mut img = ImageDim {
+            width: 0,
+            height: 0,
+        };
+        let coords: Vec<&str> = s.split(',').collect();
+        if coords.len() != 2 {
+            println!("Error : invalid Image Dim format, it must match 'w,h'.");
+            return None;
+        } else {
+            img.width = match coords[0].parse() {
+                Ok(coord) => coord,
+                Err(_)    => {
+                    println!("Error : invalid Plan format, only numbers are accepted.");
+                    return None;
+                }
+            };
+            img.height = match coords[1].parse() {
+                Ok(coord) => coord,
+                Err(_)    => {
+                    println!("Error : invalid Plan format, only numbers are accepted.");
+                    return None;
+                }
+            };
+        }
+        Some(img)
+    }
+}
+
+pub fn scale(x: i32, y: i32, image_dim: &ImageDim, plan: &Plan) -> c64 {
+    c64::new(x as f64 * plan.width / image_dim.width as f64 + plan.left, y as f64 * plan.height / image_dim.height as f64 + plan.up)
+}
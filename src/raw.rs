@@ -0,0 +1,244 @@
+//! Raw iteration data, for analysis outside of the program.
+//!
+//! The raw format is a 56 bytes header followed by two arrays, everything
+//! being little-endian :
+//!
+//! | offset | type     | content                                    |
+//! |--------|----------|--------------------------------------------|
+//! | 0      | 4 bytes  | magic number `MBRW`                        |
+//! | 4      | u32      | format version, currently 1                |
+//! | 8      | f64 x 4  | the Plan : up, left, width, height         |
+//! | 40     | u32 x 2  | the ImageDim : width, height               |
+//! | 48     | u32      | max_iter                                   |
+//! | 52     | u32      | flags, bit 0 : the roots follow            |
+//! | 56     | u32 x N  | escape counts, row by row                  |
+//! | 56+4N  | f32 x N  | smooth iteration counts, row by row        |
+//! | 56+8N  | u8 x N   | roots, row by row, if bit 0 of flags is set|
+//!
+//! where `N = width*height`. Points of the set have an escape count (and a
+//! smooth value) equal to max_iter. The roots, written for the Newton
+//! fractals, are the index + 1 of the root reached, 0 meaning none. With
+//! NumPy :
+//!
+//! ```python
+//! w, h = numpy.fromfile("data.raw", "<u4", 2, offset=40)
+//! iters = numpy.fromfile("data.raw", "<u4", w*h, offset=56).reshape(h, w)
+//! smooth = numpy.fromfile("data.raw", "<f4", w*h, offset=56+4*w*h).reshape(h, w)
+//! ```
+//!
+//! The `.npy` output holds the same arrays as a single structured array of
+//! shape `(height, width)` with the fields `iter`, `smooth` and, for the
+//! Newton fractals, `root`, but not the header values.
+
+use std::cmp;
+use std::io::{self, Read, Write};
+use plan::Plan;
+use buffer::IterationBuffer;
+
+const MAGIC: &[u8; 4] = b"MBRW";
+const VERSION: u32 = 1;
+const HAS_ROOTS: u32 = 1;
+/// Larger images are taken for a corrupt header, 16384x16384 fitting.
+const MAX_PIXELS: usize = 1 << 28;
+/// Higher max_iter values are taken for a corrupt header too, no render
+/// getting anywhere near them.
+const MAX_ITER: u32 = 1 << 30;
+
+/// Writes the iteration buffer and the Plan it was computed on in the raw
+/// format.
+pub fn write_raw<W: Write>(writer: &mut W, plan: &Plan, iterations: &IterationBuffer) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    for value in &[plan.up, plan.left, plan.width, plan.height] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&(iterations.width as u32).to_le_bytes())?;
+    writer.write_all(&(iterations.height as u32).to_le_bytes())?;
+    writer.write_all(&iterations.max_iter.to_le_bytes())?;
+    let flags = if iterations.get_roots().is_empty() { 0 } else { HAS_ROOTS };
+    writer.write_all(&flags.to_le_bytes())?;
+    for iter in iterations.get() {
+        writer.write_all(&iter.to_le_bytes())?;
+    }
+    for smooth in iterations.get_smooth() {
+        writer.write_all(&smooth.to_le_bytes())?;
+    }
+    writer.write_all(iterations.get_roots())
+}
+
+/// Reads back a file written by `write_raw`.
+pub fn read_raw<R: Read>(reader: &mut R) -> io::Result<(Plan, IterationBuffer)> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a raw iteration file"));
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported raw format version {}", version)));
+    }
+    let plan = Plan {
+        up: read_f64(reader)?,
+        left: read_f64(reader)?,
+        width: read_f64(reader)?,
+        height: read_f64(reader)?,
+    };
+    let width = read_u32(reader)? as usize;
+    let height = read_u32(reader)? as usize;
+    let max_iter = read_u32(reader)?;
+    let flags = read_u32(reader)?;
+    let pixels = match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => pixels,
+        _                                    => return Err(invalid_data(&format!("invalid image size {}x{}", width, height))),
+    };
+    if max_iter > MAX_ITER {
+        return Err(invalid_data(&format!("invalid max_iter {}", max_iter)));
+    }
+
+    //the escape counts grow as they are read : a truncated file fails before taking all the memory
+    let mut iterations = Vec::with_capacity(cmp::min(pixels, 1 << 20));
+    for _ in 0..pixels {
+        let iter = read_u32(reader)?;
+        if iter > max_iter {
+            return Err(invalid_data(&format!("escape count {} above max_iter {}", iter, max_iter)));
+        }
+        iterations.push(iter);
+    }
+    let mut smooth = Vec::with_capacity(pixels);
+    for _ in 0..pixels {
+        smooth.push(f32::from_bits(read_u32(reader)?));
+    }
+    let buffer = IterationBuffer::from_vec(iterations, smooth, width, height, max_iter);
+    if flags & HAS_ROOTS != 0 {
+        let mut roots = vec![0; pixels];
+        reader.read_exact(&mut roots)?;
+        Ok((plan, buffer.with_roots(roots)))
+    } else {
+        Ok((plan, buffer))
+    }
+}
+
+/// Writes the iteration buffer as a NumPy `.npy` file (format version 1.0).
+pub fn write_npy<W: Write>(writer: &mut W, iterations: &IterationBuffer) -> io::Result<()> {
+    let roots = iterations.get_roots();
+    let root_field = if roots.is_empty() { "" } else { ", ('root', 'u1')" };
+    let mut header = format!("{{'descr': [('iter', '<u4'), ('smooth', '<f4'){}], 'fortran_order': False, 'shape': ({}, {}), }}",
+                             root_field, iterations.height, iterations.width);
+    //magic (6) + version (2) + header length (2) + header, padded to 64 bytes
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for (index, (iter, smooth)) in iterations.get().iter().zip(iterations.get_smooth()).enumerate() {
+        writer.write_all(&iter.to_le_bytes())?;
+        writer.write_all(&smooth.to_le_bytes())?;
+        if let Some(root) = roots.get(index) {
+            writer.write_all(&[*root])?;
+        }
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+/// The error of a file that isn't in the expected format, also used by the
+/// history and bookmark files.
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample() -> IterationBuffer {
+        IterationBuffer::from_vec(vec![0, 3, 100, 7, 100, 1], vec![0.5, 3.25, 100., 7.75, 100., 1.5], 3, 2, 100)
+    }
+
+    fn header(width: u32, height: u32, max_iter: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        for value in &[-1f64, -2., 3., 2.] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in &[width, height, max_iter, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let plan = Plan {up: -0.5, left: -1.25, width: 0.75, height: 0.5};
+        let mut bytes = Vec::new();
+        write_raw(&mut bytes, &plan, &sample()).unwrap();
+        assert_eq!(bytes.len(), 56 + 8*6);
+
+        let (read_plan, read) = read_raw(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!((read_plan.up, read_plan.left, read_plan.width, read_plan.height), (-0.5, -1.25, 0.75, 0.5));
+        assert_eq!((read.width, read.height, read.max_iter), (3, 2, 100));
+        assert_eq!(read.get(), sample().get());
+        assert_eq!(read.get_smooth(), sample().get_smooth());
+        assert!(read.get_roots().is_empty());
+    }
+
+    #[test]
+    fn round_trip_with_roots() {
+        let mut bytes = Vec::new();
+        write_raw(&mut bytes, &Plan::default(), &sample().with_roots(vec![1, 2, 0, 3, 0, 1])).unwrap();
+        let (_, read) = read_raw(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(read.get_roots(), &[1, 2, 0, 3, 0, 1]);
+    }
+
+    #[test]
+    fn huge_size_is_invalid() {
+        let err = read_raw(&mut Cursor::new(header(u32::MAX, u32::MAX, 100))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn huge_max_iter_is_invalid() {
+        let err = read_raw(&mut Cursor::new(header(1, 1, 4000000000))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn escape_count_above_max_iter_is_invalid() {
+        let mut bytes = header(2, 1, 10);
+        for value in &[3u32, 499, 0, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let err = read_raw(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let mut bytes = header(1000, 1000, 100);
+        bytes.extend_from_slice(&[0; 16]);
+        assert!(read_raw(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn bad_magic_is_invalid() {
+        let mut bytes = header(1, 1, 100);
+        bytes[0] = b'X';
+        let err = read_raw(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
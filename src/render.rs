@@ -0,0 +1,249 @@
+use std::cmp;
+use std::sync::Arc;
+use std::thread;
+use complex::*;
+use plan::{Plan, ImageDim, scale};
+use fractal::Fractal;
+use cmath;
+use buffer::IterationBuffer;
+
+/// Number of threads used when the caller doesn't ask for a specific count.
+pub const DEFAULT_THREADS: usize = 4;
+
+/// Squared distance between two iterations below which a converging orbit
+/// stops.
+const TOLERANCE: f64 = 1e-12;
+
+/// Squared distance below which two points of a bounded orbit are the same,
+/// for the period detection.
+const PERIOD_TOLERANCE: f64 = 1e-10;
+
+/// The longest cycle looked for at the end of a bounded orbit.
+const MAX_PERIOD: usize = 1024;
+
+/// What happened to the orbit of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Escape {
+    /// Iterations done before `z` escaped or converged, `max_iter` if it
+    /// never did.
+    pub iter: u32,
+    /// The same count, continuous across the iteration bands.
+    pub smooth: f32,
+    /// The attractor reached, for the converging fractals.
+    pub root: Option<u8>,
+}
+
+/// Iterates the fractal until `z` escapes, or converges for the fractals that
+/// converge.
+pub fn escape_time<F: Fractal + ?Sized>(pixel: c64, fractal: &F, max_iter: u32) -> Escape {
+    if fractal.inside(pixel) {
+        return Escape {
+            iter: max_iter,
+            smooth: max_iter as f32,
+            root: None,
+        };
+    }
+    let (mut z, c) = fractal.init(pixel);
+    let converges = fractal.converges();
+    let mut iter = 0;
+    while !fractal.escaped(z) && iter < max_iter {
+        iter += 1;
+        let next = fractal.step(z, c, pixel);
+        if converges {
+            let distance = cmath::norm_sqr(next - z);
+            if distance < TOLERANCE {
+                return Escape {
+                    iter,
+                    smooth: converged_smooth_iter(distance, iter),
+                    root: fractal.root(next),
+                };
+            }
+        }
+        z = next;
+    }
+    Escape {
+        iter,
+        smooth: smooth_iter(z, iter, max_iter, fractal.degree()),
+        root: None,
+    }
+}
+
+/// The points `z0, z1, ...` of the orbit of a pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orbit {
+    pub points: Vec<c64>,
+    pub escaped: bool,
+    /// The length of the cycle a bounded orbit ended in, 1 for a fixed point
+    /// or a converged orbit.
+    pub period: Option<usize>,
+}
+
+/// Iterates the fractal like `escape_time`, keeping every point. Unlike it,
+/// the points known to be inside are iterated too.
+pub fn orbit<F: Fractal + ?Sized>(pixel: c64, fractal: &F, max_iter: u32) -> Orbit {
+    let (mut z, c) = fractal.init(pixel);
+    let mut points = vec![z];
+    while !fractal.escaped(z) && points.len() <= max_iter as usize {
+        let next = fractal.step(z, c, pixel);
+        points.push(next);
+        if fractal.converges() && cmath::norm_sqr(next - z) < TOLERANCE {
+            break;
+        }
+        z = next;
+    }
+    let escaped = fractal.escaped(z);
+    let period = if escaped { None } else { period(&points) };
+    Orbit {
+        points,
+        escaped,
+        period,
+    }
+}
+
+/// The smallest `p` such that the last point comes back `p` iterations
+/// earlier.
+fn period(points: &[c64]) -> Option<usize> {
+    let last = *points.last()?;
+    (1..cmp::min(points.len(), MAX_PERIOD + 1))
+        .find(|&p| cmath::norm_sqr(points[points.len() - 1 - p] - last) < PERIOD_TOLERANCE)
+}
+
+/// Smooth iteration count of a converged orbit : the distance between the
+/// last two iterations roughly squares at each step near the attractor, so
+/// `log2(ln(distance)/ln(TOLERANCE))` goes from 0 to 1 between two counts.
+fn converged_smooth_iter(distance: f64, iter: u32) -> f32 {
+    let overshoot = (distance.ln()/TOLERANCE.ln()).log2().clamp(0., 1.);
+    (iter as f64 - overshoot).max(0.) as f32
+}
+
+/// Renormalized iteration count `n + 1 - log_d(log2|z|)`, continuous across
+/// the iteration bands. Points of the set keep `max_iter`.
+pub fn smooth_iter(z: c64, iter: u32, max_iter: u32, degree: f64) -> f32 {
+    if iter == max_iter {
+        return max_iter as f32;
+    }
+    let log_modulus = (z.re()*z.re()+z.im()*z.im()).ln()/2.;
+    let nu = (log_modulus/::std::f64::consts::LN_2).ln()/degree.ln();
+    (iter as f64 + 1. - nu).max(0.) as f32
+}
+
+/// Computes the escape time of every pixel. The image is cut in horizontal
+/// bands, one per thread. The roots reached are kept for the fractals that
+/// converge.
+pub fn compute_iterations(fractal: &Arc<dyn Fractal>, plan: &Plan, image_dim: &ImageDim, max_iter: u32, threads: usize) -> IterationBuffer {
+    let threads = cmp::max(1, cmp::min(threads, image_dim.height));
+    let band_height = image_dim.height.div_ceil(threads);
+
+    let bands: Vec<_> = (0..threads).map(|band| {
+        let dim = image_dim.clone();
+        let plan = plan.clone();
+        let fractal = fractal.clone();
+        thread::spawn(move || {
+            let first_row = cmp::min(band*band_height, dim.height);
+            let last_row = cmp::min(first_row + band_height, dim.height);
+            let mut iterations = Vec::with_capacity((last_row - first_row)*dim.width);
+            let mut smooth = Vec::with_capacity((last_row - first_row)*dim.width);
+            let mut roots = Vec::new();
+            for j in first_row..last_row {
+                for i in 0..dim.width {
+                    let pixel = scale(i as i32, j as i32, &dim, &plan);
+                    let escape = escape_time(pixel, &*fractal, max_iter);
+                    iterations.push(escape.iter);
+                    smooth.push(escape.smooth);
+                    if fractal.converges() {
+                        roots.push(encode_root(escape.root));
+                    }
+                }
+            }
+            (iterations, smooth, roots)
+        })
+    }).collect();
+
+    let mut iterations = Vec::with_capacity(image_dim.width*image_dim.height);
+    let mut smooth = Vec::with_capacity(image_dim.width*image_dim.height);
+    let mut roots = Vec::new();
+    for band in bands {
+        let (band_iterations, band_smooth, band_roots) = band.join().unwrap();
+        iterations.extend(band_iterations);
+        smooth.extend(band_smooth);
+        roots.extend(band_roots);
+    }
+    let buffer = IterationBuffer::from_vec(iterations, smooth, image_dim.width, image_dim.height, max_iter);
+    if fractal.converges() {
+        buffer.with_roots(roots)
+    } else {
+        buffer
+    }
+}
+
+/// Escape times of the image moved by `(dx, dy)` pixels, `plan` being the
+/// frame after the move : the pixels still in the image are taken from
+/// `iterations`, only the uncovered strips are computed, split between the
+/// threads.
+pub fn shift_iterations(fractal: &Arc<dyn Fractal>, iterations: &IterationBuffer, dx: i32, dy: i32, plan: &Plan, threads: usize) -> IterationBuffer {
+    let image_dim = ImageDim {width: iterations.width, height: iterations.height};
+    let max_iter = iterations.max_iter;
+    //the pixel (i, j) shows what was at (i - dx, j - dy)
+    let source = |i: usize, j: usize| {
+        let (x, y) = (i as i64 - dx as i64, j as i64 - dy as i64);
+        if x >= 0 && y >= 0 && (x as usize) < image_dim.width && (y as usize) < image_dim.height {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    };
+
+    let mut uncovered = Vec::new();
+    for j in 0..image_dim.height {
+        for i in 0..image_dim.width {
+            if source(i, j).is_none() {
+                uncovered.push((i, j));
+            }
+        }
+    }
+    let threads = cmp::max(1, cmp::min(threads, uncovered.len()));
+    let chunk_len = cmp::max(1, uncovered.len().div_ceil(threads));
+    let chunks: Vec<_> = uncovered.chunks(chunk_len).map(|chunk| {
+        let chunk = chunk.to_vec();
+        let dim = image_dim.clone();
+        let plan = plan.clone();
+        let fractal = fractal.clone();
+        thread::spawn(move || {
+            chunk.iter()
+                .map(|&(i, j)| escape_time(scale(i as i32, j as i32, &dim, &plan), &*fractal, max_iter))
+                .collect::<Vec<_>>()
+        })
+    }).collect();
+    let mut computed = chunks.into_iter().flat_map(|chunk| chunk.join().unwrap());
+
+    let mut shifted = Vec::with_capacity(image_dim.width*image_dim.height);
+    let mut smooth = Vec::with_capacity(image_dim.width*image_dim.height);
+    let mut roots = Vec::new();
+    for j in 0..image_dim.height {
+        for i in 0..image_dim.width {
+            let (iter, smooth_iter, root) = match source(i, j) {
+                Some((x, y)) => (iterations.at(x, y), iterations.smooth_at(x, y), iterations.root_at(x, y)),
+                None         => {
+                    let escape = computed.next().unwrap();
+                    (escape.iter, escape.smooth, escape.root)
+                },
+            };
+            shifted.push(iter);
+            smooth.push(smooth_iter);
+            if fractal.converges() {
+                roots.push(encode_root(root));
+            }
+        }
+    }
+    let buffer = IterationBuffer::from_vec(shifted, smooth, image_dim.width, image_dim.height, max_iter);
+    if fractal.converges() {
+        buffer.with_roots(roots)
+    } else {
+        buffer
+    }
+}
+
+/// Roots are stored as their index + 1, 0 meaning none.
+fn encode_root(root: Option<u8>) -> u8 {
+    root.map_or(0, |root| root.saturating_add(1))
+}
@@ -0,0 +1,903 @@
+use sfml::graphics::{Texture, Sprite, RenderWindow, RenderTarget, Image, Color, RectangleShape, Font, Text, VertexArray, Vertex, LinesStrip, View, FloatRect};
+use sfml::system::Vector2f;
+use sfml::window::{ContextSettings, VideoMode, event};
+use sfml::window::keyboard::{self, Key};
+use sfml::window::mouse::MouseButton;
+use sfml::window::WindowStyle;
+use mandelbrot::plan::{Plan, ImageDim, scale};
+use mandelbrot::buffer::{self, PixelArrayBuffer};
+use mandelbrot::render::{compute_iterations, shift_iterations, orbit, DEFAULT_THREADS};
+use mandelbrot::fractal::{Fractal, Julia};
+use mandelbrot::palette::{Palette, Coloring, colorize};
+use mandelbrot::encode::write_png_text;
+use mandelbrot::history::{History, Location};
+use mandelbrot::bookmark::{Bookmark, read_bookmarks, write_bookmarks};
+use complex::*;
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use {parse_options, parse_coloring, parse_fractal, parse_plan};
+
+/// The options saved in the bookmarks, which make the fractal.
+const FRACTAL_OPTIONS: [&str; 6] = ["fractal", "formula", "julia", "power", "polynomial", "relaxation"];
+
+/// Fonts tried for the labels when `--font` isn't given.
+const SYSTEM_FONTS: [&str; 5] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// The history and the bookmarks are kept in the home directory, or in the
+/// current one without it.
+fn home_file(name: &str) -> PathBuf {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from).unwrap_or_default().join(name)
+}
+
+fn history_path() -> PathBuf {
+    home_file(".mandelbrot_history")
+}
+
+fn bookmarks_path() -> PathBuf {
+    home_file(".mandelbrot_bookmarks")
+}
+
+/// The history of the last session, if it explored the same fractal, with
+/// the starting view on top of it.
+fn load_history(fractal: &str, location: Location) -> History {
+    match File::open(history_path()).and_then(|file| History::read(BufReader::new(file))) {
+        Ok(mut history) if history.fractal() == fractal => {
+            history.visit(location);
+            history
+        },
+        _ => History::new(fractal, location),
+    }
+}
+
+fn save_history(history: &History) {
+    let path = history_path();
+    let result = File::create(&path).and_then(|file| history.write(&mut BufWriter::new(file)));
+    if let Err(err) = result {
+        println!("Error : couldn't write the history `{}` : {}", path.display(), err);
+    }
+}
+
+/// The saved bookmarks, none if the file doesn't exist yet.
+fn load_bookmarks() -> Vec<Bookmark> {
+    let path = bookmarks_path();
+    match File::open(&path) {
+        Ok(file) => read_bookmarks(BufReader::new(file)).unwrap_or_else(|err| {
+            println!("Error : couldn't read the bookmarks `{}` : {}", path.display(), err);
+            Vec::new()
+        }),
+        Err(_)   => Vec::new(),
+    }
+}
+
+fn save_bookmarks(bookmarks: &[Bookmark]) {
+    let path = bookmarks_path();
+    let result = File::create(&path).and_then(|file| write_bookmarks(&mut BufWriter::new(file), bookmarks));
+    if let Err(err) = result {
+        println!("Error : couldn't write the bookmarks `{}` : {}", path.display(), err);
+    }
+}
+
+/// The fractal options of the view, with the c of the open Julia set.
+fn view_options(fractal_options: &[(String, String)], julia_c: Option<c64>) -> Vec<(String, String)> {
+    let mut options = fractal_options.to_vec();
+    if let Some(c) = julia_c {
+        options.push(("julia".to_string(), format!("{},{}", c.re(), c.im())));
+    }
+    options
+}
+
+/// What the history is kept for : the options that make the fractal, like
+/// `--fractal tricorn --power 3`, empty for the Mandelbrot set.
+fn history_key(fractal_options: &[(String, String)]) -> String {
+    fractal_options.iter().map(|(option, value)| format!("--{} {}", option, value)).collect::<Vec<_>>().join(" ")
+}
+
+/// The magnification, with an exponent when it gets long.
+fn magnification_label(magnification: f64) -> String {
+    if magnification < 1e5 {
+        format!("x{:.2}", magnification)
+    } else {
+        format!("x{:.3e}", magnification)
+    }
+}
+
+/// The size of the snapshots : a multiple of the window, like `4x`, or a
+/// fixed size.
+#[derive(Debug, Clone)]
+enum SnapshotSize {
+    Scale(f64),
+    Fixed(ImageDim),
+}
+
+impl SnapshotSize {
+    fn from_string(s: &str) -> Option<SnapshotSize> {
+        match s.strip_suffix('x') {
+            Some(factor) => match factor.parse::<f64>() {
+                Ok(factor) if factor > 0. => Some(SnapshotSize::Scale(factor)),
+                _                         => {
+                    println!("Error : invalid snapshot size, it must be like '4x' or 'width,height'.");
+                    None
+                }
+            },
+            None         => ImageDim::from_string(s).map(SnapshotSize::Fixed),
+        }
+    }
+
+    fn of(&self, window: &ImageDim) -> ImageDim {
+        match *self {
+            SnapshotSize::Scale(factor)     => ImageDim {
+                width: cmp::max(1, (window.width as f64*factor).round() as usize),
+                height: cmp::max(1, (window.height as f64*factor).round() as usize),
+            },
+            SnapshotSize::Fixed(ref dim) => dim.clone(),
+        }
+    }
+}
+
+/// The current UTC date and time, like `20261019-143052`.
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+    let (days, time) = (seconds/86400, seconds % 86400);
+    //days since 1970-01-01 to a civil date, counting from the 1st of March so the leap day is last
+    let days = days as i64 + 719468;
+    let era = days/146097;
+    let day_of_era = days - era*146097;
+    let year_of_era = (day_of_era - day_of_era/1460 + day_of_era/36524 - day_of_era/146096)/365;
+    let day_of_year = day_of_era - (365*year_of_era + year_of_era/4 - year_of_era/100);
+    let month = (5*day_of_year + 2)/153;
+    let day = day_of_year - (153*month + 2)/5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era*400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time/3600, time % 3600/60, time % 60)
+}
+
+/// Quotes a value for the shell when it needs it, like a formula.
+fn shell_word(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "+-.,_".contains(c)) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Creates a new snapshot file, named after the time. The snapshots of the
+/// same second get a counter, like `snapshot-20261019-143052-2.png`.
+fn create_snapshot_file() -> io::Result<(String, File)> {
+    let stamp = timestamp();
+    let mut counter = 1;
+    loop {
+        let path = if counter == 1 { format!("snapshot-{}.png", stamp) } else { format!("snapshot-{}-{}.png", stamp, counter) };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file)                                                   => return Ok((path, file)),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(err)                                                   => return Err(err),
+        }
+    }
+}
+
+/// Renders the view in the background and writes it to a timestamped PNG,
+/// with the `generate` command that renders it again, `options` included, in
+/// its text chunks.
+fn snapshot(fractal: Arc<dyn Fractal>, plan: Plan, image_dim: ImageDim, max_iter: u32, palette: Palette, coloring: Coloring, options: Vec<(String, String)>) {
+    //the file is created right away, so that the next snapshot takes another name
+    let (path, file) = match create_snapshot_file() {
+        Ok(created) => created,
+        Err(err)    => {
+            println!("Error : couldn't create the snapshot : {}", err);
+            return;
+        }
+    };
+    let mut command = format!("generate {},{},{},{} {},{} {} {}",
+                              plan.up, plan.left, plan.width, plan.height,
+                              image_dim.width, image_dim.height,
+                              max_iter, path);
+    for (option, value) in options {
+        command.push_str(&format!(" --{} {}", option, shell_word(&value)));
+    }
+    println!("SNAPSHOT: rendering `{}` ({}x{})", path, image_dim.width, image_dim.height);
+    thread::spawn(move || {
+        let iterations = compute_iterations(&fractal, &plan, &image_dim, max_iter, DEFAULT_THREADS);
+        let pixel_buffer = colorize(&iterations, &palette, coloring, &buffer::Color::new_rgb(0, 0, 0), 0.);
+        let mut writer = BufWriter::new(file);
+        let result = write_png_text(&mut writer, &pixel_buffer, &[("Software", "rust-mandelbrot"), ("Parameters", &command)])
+            .and_then(|_| writer.flush());
+        match result {
+            Ok(())   => println!("SNAPSHOT: saved `{}`", path),
+            Err(err) => println!("Error : couldn't write `{}` : {}", path, err),
+        }
+    });
+}
+
+pub fn to_image(pixel_buffer: &PixelArrayBuffer) -> Image {
+    Image::create_from_pixels(pixel_buffer.width as u32, pixel_buffer.height as u32, pixel_buffer.get()).unwrap()
+}
+
+/// Widens the plan around its center to the aspect ratio of the image, so
+/// that it isn't stretched.
+fn fit_to(plan: &Plan, image_dim: &ImageDim) -> Plan {
+    let aspect = image_dim.width as f64/image_dim.height as f64;
+    let (width, height) = if plan.width/plan.height < aspect {
+        (plan.height*aspect, plan.height)
+    } else {
+        (plan.width, plan.width/aspect)
+    };
+    Plan {
+        up: plan.up + (plan.height - height)/2.,
+        left: plan.left + (plan.width - width)/2.,
+        width,
+        height,
+    }
+}
+
+/// The rectangle with the aspect ratio of the window that grows from `start`
+/// toward `end` until it reaches it, as `(left, up, width, height)` in pixels.
+fn selection(start: (i32, i32), end: (i32, i32), image_dim: &ImageDim) -> (f64, f64, f64, f64) {
+    let aspect = image_dim.width as f64/image_dim.height as f64;
+    let (dx, dy) = ((end.0 - start.0) as f64, (end.1 - start.1) as f64);
+    let width = dx.abs().max(dy.abs()*aspect);
+    let height = width/aspect;
+    let left = if dx < 0. { start.0 as f64 - width } else { start.0 as f64 };
+    let up = if dy < 0. { start.1 as f64 - height } else { start.1 as f64 };
+    (left, up, width, height)
+}
+
+/// The size in pixels of the rectangle a click zooms into : 90 pixels wide,
+/// with the aspect ratio of the window.
+fn click_size(image_dim: &ImageDim) -> (f64, f64) {
+    (90., 90.*image_dim.height as f64/image_dim.width as f64)
+}
+
+/// Counts a zoom by `magnification` in the zoom level, which goes up by one
+/// every 10x : `depth` keeps what is left of a level.
+fn deepen(zoom_lvl: &mut u32, depth: &mut f64, magnification: f64) {
+    *depth += magnification.log10();
+    while *depth >= 1. {
+        *zoom_lvl += 1;
+        *depth -= 1.;
+    }
+    while *depth <= -1. {
+        *zoom_lvl = zoom_lvl.saturating_sub(1);
+        *depth += 1.;
+    }
+}
+
+/// The inset takes a quarter of the window, in its top right corner.
+fn inset_size(image_dim: &ImageDim) -> ImageDim {
+    ImageDim {width: cmp::max(1, image_dim.width/4), height: cmp::max(1, image_dim.height/4)}
+}
+
+/// Loads the font of the labels, `None` if there is none : the labels are
+/// printed instead.
+fn load_font(path: Option<&String>) -> Option<Font> {
+    match path {
+        Some(path) => {
+            let font = Font::new_from_file(path);
+            if font.is_none() {
+                println!("Error : couldn't load the font `{}`, the labels will be printed.", path);
+            }
+            font
+        },
+        None       => SYSTEM_FONTS.iter().filter_map(|path| Font::new_from_file(path)).next(),
+    }
+}
+
+pub fn draw_main(args: Vec<String>) {
+    let (mut args, mut options) = match parse_options(args, &["palette", "coloring", "fractal", "formula", "julia", "power", "polynomial", "relaxation", "font", "zoom-factor", "bookmark", "snapshot"]) {
+        Some(parsed) => parsed,
+        None         => return,
+    };
+    //a bookmark gives the plan, max_iter and the fractal options that aren't on the command line
+    let bookmarked = options.contains_key("bookmark");
+    if let Some(name) = options.get("bookmark").cloned() {
+        if args.len() > 3 {
+            println!();
+            println!("Error : with `--bookmark`, the `draw` command only takes the window size");
+            println!("See `help draw` to get specific help");
+            return;
+        }
+        let bookmark = match load_bookmarks().into_iter().find(|bookmark| bookmark.name == name) {
+            Some(bookmark) => bookmark,
+            None           => {
+                println!("Error : no bookmark `{}` in `{}`", name, bookmarks_path().display());
+                return;
+            }
+        };
+        for (option, value) in bookmark.options {
+            options.entry(option).or_insert(value);
+        }
+        let window = args.get(2).cloned().unwrap_or_else(|| "?".to_string());
+        let plan = &bookmark.plan;
+        args.truncate(2);
+        args.push(format!("{},{},{},{}", plan.up, plan.left, plan.width, plan.height));
+        args.push(window);
+        args.push(bookmark.max_iter.to_string());
+    }
+    let (palette, coloring) = match parse_coloring(&options) {
+        Some(parsed) => parsed,
+        None         => return,
+    };
+    let mut fractal = match parse_fractal(&options) {
+        Some(fractal) => fractal,
+        None          => return,
+    };
+    let zoom_factor = match options.get("zoom-factor").map(|factor| factor.parse::<f64>()) {
+        None                            => 1.25,
+        Some(Ok(factor)) if factor > 1. => factor,
+        Some(_)                         => {
+            println!("Error : invalid zoom factor, it must be a number above 1.");
+            return;
+        }
+    };
+    let snapshot_size = match options.get("snapshot") {
+        Some(size) => match SnapshotSize::from_string(size) {
+            Some(size) => size,
+            None       => return,
+        },
+        None       => SnapshotSize::Scale(4.),
+    };
+    if args.len() != 5 {
+        println!();
+        println!("Error : the `draw` command requires 3 arguments");
+        println!("See `help draw` to get specific help");
+    } else {
+        let option_plan = parse_plan(&args[2], &fractal);
+        if let Some(mut plan) = option_plan {
+            let option_image = ImageDim::from_string(&args[3]);
+            if let Some(mut image_dim) = option_image {
+                if bookmarked {
+                    plan = fit_to(&plan, &image_dim);
+                }
+                //Dessin :)
+                if let Ok(mut max_iter) = args[4].parse::<u32>() {
+                    let mut window = RenderWindow::new(VideoMode::new_init(image_dim.width as u32, image_dim.height as u32, 32),
+                                                   "Mandelbrot",
+                                                   WindowStyle::DefaultStyle,
+                                                   &ContextSettings::default()).expect("Couldn't create RenderWindow");
+                    window.set_framerate_limit(30);
+
+                    let set_color = buffer::Color::new_rgb(0, 0, 0);
+                    let mut redraw = false; //should we redraw the set ?
+                    let start = Instant::now();
+                    let mut iterations = compute_iterations(&fractal, &plan, &image_dim, max_iter, DEFAULT_THREADS);
+                    let mut render_time = start.elapsed(); //of the last image, or of its uncovered strips
+                    let mut hud = false; //H shows the coordinates and the render settings in the top left corner
+                    let mut default_plan = fractal.default_plan(); //of Home and of the HUD's zoom, fitting it can take a while
+                    let mut tex = Texture::new_from_image(&to_image(&colorize(&iterations, &palette, coloring, &set_color, 0.))).unwrap();
+
+                    //palette cycling : the offset moves by `cycle_speed` every frame
+                    let mut cycling = false;
+                    let mut cycle_speed = 0.005f32;
+                    let mut cycle_offset = 0.0f32;
+
+                    //panning : the texture follows the drag, the uncovered strips are computed on release
+                    let mut drag: Option<(i32, i32)> = None;
+                    let mut pan = (0, 0);
+                    let mut shift: Option<(i32, i32)> = None; //the move of the next frame, in pixels
+
+                    let mut zoom_lvl: u32 = 0;
+                    let mut depth = 0f64; //the zooms of less than a level, from the wheel and the selections
+                    let mut select: Option<(i32, i32)> = None; //the corner the zoom rectangle is dragged from
+                    let mut mouse = (0, 0);
+
+                    //bookmarks : K saves the view under a name typed in the window, L lists them
+                    let mut fractal_options: Vec<(String, String)> = FRACTAL_OPTIONS.iter()
+                        .filter_map(|&option| options.get(option).map(|value| (option.to_string(), value.clone())))
+                        .collect();
+                    let mut bookmarks = load_bookmarks();
+                    let mut naming: Option<String> = None; //the name being typed
+                    let mut skip_text = false; //the K that starts the name is typed too
+                    let mut listing: Option<usize> = None; //the bookmark selected in the list
+                    let mut julia_c: Option<c64> = None; //the c of the open Julia set, saved as `--julia`
+
+                    //views visited : B and F move in them, right-click and Home go back to the default view
+                    let mut history = load_history(&history_key(&fractal_options), Location {plan: plan.clone(), zoom_lvl});
+                    let mut visit = false; //should the next view be added to the history ?
+
+                    //orbit of the point under the cursor, while LControl is held
+                    let font = load_font(options.get("font"));
+                    let mut orbit_label = String::new();
+
+                    //Julia inset : the Julia set of the c under the cursor, in
+                    //the top right corner. J opens it in the whole window.
+                    let mut julia_family = !options.contains_key("julia");
+                    let mut inset = julia_family;
+                    let mut inset_dim = inset_size(&image_dim);
+                    let mut inset_plan = fractal.julia_plan().unwrap_or_else(|| default_plan.clone());
+                    let mut inset_c = None; //the c of the next inset to compute
+                    let mut inset_tex = None;
+                    let mut parent: Option<(Arc<dyn Fractal>, Plan, u32)> = None; //the set the open Julia set comes from
+                    let mut inset_frame = RectangleShape::new_init(&Vector2f::new(inset_dim.width as f32, inset_dim.height as f32)).unwrap();
+                    inset_frame.set_position(&Vector2f::new(image_dim.width.saturating_sub(inset_dim.width + 10) as f32, 10.));
+                    inset_frame.set_fill_color(&Color::new_rgba(0, 0, 0, 0));
+                    inset_frame.set_outline_color(&Color::new_rgb(255, 255, 255));
+                    inset_frame.set_outline_thickness(1.);
+                    let (click_width, click_height) = click_size(&image_dim);
+                    let mut rect = RectangleShape::new_init(&Vector2f::new(click_width as f32, click_height as f32)).unwrap();
+                    rect.set_fill_color(&Color::new_rgba(0, 0, 0, 0));
+                    rect.set_outline_color(&Color::new_rgb(255, 255, 255));
+                    rect.set_outline_thickness(1.);
+
+                    while window.is_open() {
+                        for event in window.events() {
+                            match event {
+                                event::Closed => {
+                                    save_history(&history);
+                                    window.close();
+                                },
+                                event::Resized {
+                                    width, height
+                                } if width > 0 && height > 0 => {
+                                        //same center and pixel size : the image shows more or less of the set, without stretching
+                                        let (width, height) = (width as usize, height as usize);
+                                        let center = (plan.left + plan.width/2., plan.up + plan.height/2.);
+                                        plan.width *= width as f64/image_dim.width as f64;
+                                        plan.height *= height as f64/image_dim.height as f64;
+                                        plan.left = center.0 - plan.width/2.;
+                                        plan.up = center.1 - plan.height/2.;
+                                        image_dim = ImageDim {width, height};
+                                        window.set_view(&View::new_from_rect(&FloatRect::new(0., 0., width as f32, height as f32)).unwrap());
+
+                                        inset_dim = inset_size(&image_dim);
+                                        inset_frame.set_size(&Vector2f::new(inset_dim.width as f32, inset_dim.height as f32));
+                                        inset_frame.set_position(&Vector2f::new(image_dim.width.saturating_sub(inset_dim.width + 10) as f32, 10.));
+                                        inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                                        if select.is_none() {
+                                            let (click_width, click_height) = click_size(&image_dim);
+                                            rect.set_size(&Vector2f::new(click_width as f32, click_height as f32));
+                                            rect.set_position(&Vector2f::new((mouse.0 as f64 - click_width/2.) as f32, (mouse.1 as f64 - click_height/2.) as f32));
+                                        }
+                                        if parent.is_none() {
+                                            history.replace(Location {plan: plan.clone(), zoom_lvl});
+                                        }
+                                        redraw = true;
+                                    },
+                                event::MouseButtonPressed {
+                                    button, x, y
+                                } => {
+                                        if button == MouseButton::MouseRight {
+                                            zoom_lvl = 0;
+                                            depth = 0.;
+                                            redraw = true;
+                                            visit = true;
+                                            plan = fit_to(&default_plan, &image_dim); //valeurs par défaut
+                                        } else if button == MouseButton::MouseMiddle {
+                                            drag = Some((x, y));
+                                        } else if button == MouseButton::MouseLeft && (keyboard::is_key_pressed(Key::LShift) || keyboard::is_key_pressed(Key::RShift)) {
+                                            redraw = true;
+                                            visit = true;
+                                            //dézoom : la vue entière devient le rectangle du clic, centré sur la souris
+                                            let center = scale(x, y, &image_dim, &plan);
+                                            let magnification = image_dim.width as f64/click_size(&image_dim).0;
+                                            let (width, height) = (plan.width*magnification, plan.height*magnification);
+                                            plan = Plan {up: center.im() - height/2., left: center.re() - width/2., width, height};
+                                            deepen(&mut zoom_lvl, &mut depth, 1./magnification);
+                                            println!("ZOOM: {} sur ({:.5}; {:.5})", zoom_lvl, center.re(), center.im());
+                                        } else if button == MouseButton::MouseLeft {
+                                            select = Some((x, y));
+                                        }
+                                    },
+                                event::MouseButtonReleased {
+                                    button: MouseButton::MouseLeft, x, y
+                                } if select.is_some() => {
+                                        let start = select.take().unwrap();
+                                        let (left, up, width, _) = selection(start, (x, y), &image_dim);
+                                        redraw = true;
+                                        visit = true;
+                                        if width >= 4. {
+                                            //zoom sur le rectangle sélectionné
+                                            let magnification = image_dim.width as f64/width;
+                                            plan = Plan {
+                                                up: plan.up + up*plan.height/image_dim.height as f64,
+                                                left: plan.left + left*plan.width/image_dim.width as f64,
+                                                width: plan.width/magnification,
+                                                height: plan.height/magnification,
+                                            };
+                                            deepen(&mut zoom_lvl, &mut depth, magnification);
+                                            println!("ZOOM: {} sur ({:.5}; {:.5})", zoom_lvl, plan.left+plan.width/2., plan.up+plan.height/2.);
+                                        } else {
+                                            //simple clic : zoom sur le rectangle du clic, centré sur la souris
+                                            let center = scale(start.0, start.1, &image_dim, &plan);
+                                            let magnification = image_dim.width as f64/click_size(&image_dim).0;
+                                            let (width, height) = (plan.width/magnification, plan.height/magnification);
+                                            plan = Plan {up: center.im() - height/2., left: center.re() - width/2., width, height};
+                                            deepen(&mut zoom_lvl, &mut depth, magnification);
+                                            println!("ZOOM: {} sur ({:.5}; {:.5})", zoom_lvl, plan.left+plan.width/2., plan.up+plan.height/2.);
+                                        }
+                                        let (click_width, click_height) = click_size(&image_dim);
+                                        rect.set_size(&Vector2f::new(click_width as f32, click_height as f32));
+                                        rect.set_position(&Vector2f::new((x as f64 - click_width/2.) as f32, (y as f64 - click_height/2.) as f32));
+                                    },
+                                event::MouseButtonReleased {
+                                    button: MouseButton::MouseMiddle, ..
+                                } if drag.is_some() => {
+                                        drag = None;
+                                        if pan != (0, 0) {
+                                            shift = Some(pan);
+                                            visit = true;
+                                        }
+                                    },
+                                event::MouseWheelMoved {
+                                    delta, x, y
+                                } if delta != 0 => {
+                                        redraw = true;
+                                        visit = true;
+                                        //the point under the cursor stays under it
+                                        let center = scale(x, y, &image_dim, &plan);
+                                        plan = plan.zoom_at(center, zoom_factor.powi(delta));
+                                        deepen(&mut zoom_lvl, &mut depth, zoom_factor.powi(delta));
+                                        println!("ZOOM: {} sur ({:.5}; {:.5})", zoom_lvl, center.re(), center.im());
+                                    },
+                                event::MouseMoved {
+                                    x, y
+                                } => {
+                                        mouse = (x, y);
+                                        if let Some(start) = select {
+                                            //the selection follows the cursor, with the shape of the window
+                                            let (left, up, width, height) = selection(start, (x, y), &image_dim);
+                                            rect.set_position(&Vector2f::new(left as f32, up as f32));
+                                            rect.set_size(&Vector2f::new(width as f32, height as f32));
+                                        } else {
+                                            let (click_width, click_height) = click_size(&image_dim);
+                                            rect.set_position(&Vector2f::new((x as f64 - click_width/2.) as f32, (y as f64 - click_height/2.) as f32));
+                                        }
+                                        if let Some(start) = drag {
+                                            pan = (x - start.0, y - start.1);
+                                        }
+                                        if inset && parent.is_none() {
+                                            inset_c = Some(scale(x, y, &image_dim, &plan));
+                                        }
+                                    },
+                                event::TextEntered {
+                                    code
+                                } if naming.is_some() && !code.is_control() => {
+                                        if skip_text && (code == 'k' || code == 'K') {
+                                            skip_text = false;
+                                        } else if let Some(ref mut name) = naming {
+                                            skip_text = false;
+                                            name.push(code);
+                                            if font.is_none() {
+                                                println!("BOOKMARK: {}", name);
+                                            }
+                                        }
+                                    },
+                                event::KeyPressed {
+                                    code, ..
+                                } if naming.is_some() => {
+                                        match code {
+                                            Key::Return => {
+                                                let name = naming.take().unwrap().trim().to_string();
+                                                if !name.is_empty() {
+                                                    bookmarks.retain(|bookmark| bookmark.name != name);
+                                                    bookmarks.push(Bookmark {name: name.clone(), plan: plan.clone(), max_iter: max_iter*(zoom_lvl+1), options: view_options(&fractal_options, julia_c)});
+                                                    save_bookmarks(&bookmarks);
+                                                    println!("BOOKMARK: saved `{}`", name);
+                                                }
+                                            },
+                                            Key::BackSpace => {
+                                                if let Some(ref mut name) = naming {
+                                                    name.pop();
+                                                    if font.is_none() {
+                                                        println!("BOOKMARK: {}", name);
+                                                    }
+                                                }
+                                            },
+                                            Key::Escape => {
+                                                naming = None;
+                                                println!("BOOKMARK: cancelled");
+                                            },
+                                            _ => {},
+                                        }
+                                    },
+                                event::KeyPressed {
+                                    code, ..
+                                } if listing.is_some() => {
+                                        let selected = listing.unwrap();
+                                        match code {
+                                            Key::Up | Key::Down => {
+                                                let selected = if code == Key::Up { selected.saturating_sub(1) } else { cmp::min(selected + 1, bookmarks.len() - 1) };
+                                                listing = Some(selected);
+                                                if font.is_none() {
+                                                    println!("BOOKMARKS: > {}", bookmarks[selected].name);
+                                                }
+                                            },
+                                            Key::Return => {
+                                                listing = None;
+                                                let bookmark = bookmarks[selected].clone();
+                                                let bookmark_options: HashMap<String, String> = bookmark.options.iter().cloned().collect();
+                                                //parse_fractal prints what is wrong with the options
+                                                if let Some(jumped) = parse_fractal(&bookmark_options) {
+                                                    fractal = jumped;
+                                                    default_plan = fractal.default_plan();
+                                                    fractal_options = bookmark.options.clone();
+                                                    julia_family = !bookmark_options.contains_key("julia");
+                                                    inset = inset && julia_family;
+                                                    inset_plan = fractal.julia_plan().unwrap_or_else(|| default_plan.clone());
+                                                    parent = None;
+                                                    julia_c = None;
+                                                    plan = fit_to(&bookmark.plan, &image_dim);
+                                                    max_iter = bookmark.max_iter;
+                                                    zoom_lvl = 0;
+                                                    depth = 0.;
+                                                    redraw = true;
+                                                    visit = true;
+                                                    let key = history_key(&fractal_options);
+                                                    if history.fractal() != key {
+                                                        save_history(&history);
+                                                        history = load_history(&key, Location {plan: plan.clone(), zoom_lvl});
+                                                        visit = false;
+                                                    }
+                                                    inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                                                    println!("BOOKMARK: `{}`", bookmark.name);
+                                                }
+                                            },
+                                            Key::Escape | Key::L => listing = None,
+                                            _ => {},
+                                        }
+                                    },
+                                event::KeyPressed {
+                                    code, ..
+                                } => {
+                                        match code {
+                                            Key::K => {
+                                                naming = Some(String::new());
+                                                skip_text = true;
+                                                if font.is_none() {
+                                                    println!("BOOKMARK: type the name in the window, then press Enter");
+                                                }
+                                            },
+                                            Key::L if bookmarks.is_empty() => println!("BOOKMARKS: none saved yet, press K to save the view"),
+                                            Key::L => {
+                                                listing = Some(0);
+                                                if font.is_none() {
+                                                    for bookmark in &bookmarks {
+                                                        println!("BOOKMARKS: {}", bookmark.name);
+                                                    }
+                                                    println!("BOOKMARKS: > {}", bookmarks[0].name);
+                                                }
+                                            },
+                                            Key::S => {
+                                                //the palette and coloring are given as on the command line, the rest comes from the view
+                                                let snapshot_dim = snapshot_size.of(&image_dim);
+                                                let mut snapshot_options = view_options(&fractal_options, julia_c);
+                                                snapshot_options.extend(["palette", "coloring"].iter().filter_map(|&option| options.get(option).map(|value| (option.to_string(), value.clone()))));
+                                                snapshot(fractal.clone(), fit_to(&plan, &snapshot_dim), snapshot_dim, max_iter*(zoom_lvl+1), palette.clone(), coloring, snapshot_options);
+                                            },
+                                            Key::H if font.is_none() => println!("HUD: no font to draw it, see `--font`"),
+                                            Key::H => hud = !hud,
+                                            Key::C => {
+                                                cycling = !cycling;
+                                                println!("CYCLING: {}", if cycling { "on" } else { "off" });
+                                            },
+                                            Key::R => {
+                                                cycle_speed = -cycle_speed;
+                                                println!("CYCLING: reversed");
+                                            },
+                                            Key::Add | Key::Equal => {
+                                                cycle_speed *= 1.5;
+                                                println!("CYCLING: speed {:.4}", cycle_speed.abs());
+                                            },
+                                            Key::Subtract | Key::Dash => {
+                                                cycle_speed /= 1.5;
+                                                println!("CYCLING: speed {:.4}", cycle_speed.abs());
+                                            },
+                                            Key::Left | Key::Right | Key::Up | Key::Down if drag.is_none() => {
+                                                //a tenth of the window per press, the view moves toward the arrow
+                                                let (step_x, step_y) = (image_dim.width as i32/10, image_dim.height as i32/10);
+                                                let (dx, dy) = shift.unwrap_or((0, 0));
+                                                visit = true;
+                                                shift = Some(match code {
+                                                    Key::Left  => (dx + step_x, dy),
+                                                    Key::Right => (dx - step_x, dy),
+                                                    Key::Up    => (dx, dy + step_y),
+                                                    _          => (dx, dy - step_y),
+                                                });
+                                            },
+                                            Key::Home => {
+                                                zoom_lvl = 0;
+                                                depth = 0.;
+                                                redraw = true;
+                                                visit = true;
+                                                plan = fit_to(&default_plan, &image_dim);
+                                            },
+                                            Key::B | Key::F if parent.is_none() => {
+                                                let location = if code == Key::B { history.back() } else { history.forward() };
+                                                match location {
+                                                    Some(location) => {
+                                                        plan = fit_to(&location.plan, &image_dim);
+                                                        zoom_lvl = location.zoom_lvl;
+                                                        depth = 0.;
+                                                        redraw = true;
+                                                        inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                                                        println!("HISTORY: {} sur ({:.5}; {:.5})", zoom_lvl, plan.left+plan.width/2., plan.up+plan.height/2.);
+                                                    },
+                                                    None => println!("HISTORY: no {} view", if code == Key::B { "previous" } else { "next" }),
+                                                }
+                                            },
+                                            Key::I if julia_family => {
+                                                inset = !inset;
+                                                inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                                                println!("JULIA INSET: {}", if inset { "on" } else { "off" });
+                                            },
+                                            Key::J if julia_family => {
+                                                //back to the set the Julia set was opened from, or open the one under the cursor
+                                                if let Some((base, base_plan, base_zoom)) = parent.take() {
+                                                    fractal = base;
+                                                    default_plan = fractal.default_plan();
+                                                    plan = fit_to(&base_plan, &image_dim);
+                                                    zoom_lvl = base_zoom;
+                                                    julia_c = None;
+                                                    inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                                                    println!("JULIA: closed");
+                                                } else {
+                                                    let c = scale(mouse.0, mouse.1, &image_dim, &plan);
+                                                    parent = Some((fractal.clone(), plan.clone(), zoom_lvl));
+                                                    fractal = Arc::new(Julia::new(fractal.clone(), c));
+                                                    default_plan = fractal.default_plan();
+                                                    plan = fit_to(&default_plan, &image_dim);
+                                                    zoom_lvl = 0;
+                                                    julia_c = Some(c);
+                                                    println!("JULIA: c = ({:.5}; {:.5})", c.re(), c.im());
+                                                }
+                                                redraw = true;
+                                            },
+                                            _ => {},
+                                        }
+                                    },
+                                _ => {},
+                            }
+                        }
+
+                        if redraw {
+                            let start = Instant::now();
+                            iterations = compute_iterations(&fractal,
+                                            &plan,
+                                            &image_dim,
+                                            max_iter*(zoom_lvl+1),
+                                            DEFAULT_THREADS);
+                            render_time = start.elapsed();
+                            tex = Texture::new_from_image(&to_image(&colorize(&iterations, &palette, coloring, &set_color, cycle_offset))).unwrap();
+                            redraw = false;
+                            shift = None;
+                            pan = (0, 0);
+                            if drag.is_some() {
+                                drag = Some(mouse);
+                            }
+                            if visit && parent.is_none() {
+                                history.visit(Location {plan: plan.clone(), zoom_lvl});
+                            }
+                            visit = false;
+                        } else if let Some((dx, dy)) = shift.take() {
+                            //the content moves by (dx, dy) pixels, so the frame moves the other way
+                            plan.left -= dx as f64*plan.width/image_dim.width as f64;
+                            plan.up -= dy as f64*plan.height/image_dim.height as f64;
+                            let start = Instant::now();
+                            iterations = shift_iterations(&fractal, &iterations, dx, dy, &plan, DEFAULT_THREADS);
+                            render_time = start.elapsed();
+                            let pixel_buffer = colorize(&iterations, &palette, coloring, &set_color, cycle_offset);
+                            tex.update_from_pixels(pixel_buffer.get(), pixel_buffer.width as u32, pixel_buffer.height as u32, 0, 0);
+                            pan = (0, 0);
+                            inset_c = Some(scale(mouse.0, mouse.1, &image_dim, &plan));
+                            if visit && parent.is_none() {
+                                history.visit(Location {plan: plan.clone(), zoom_lvl});
+                            }
+                            visit = false;
+                            println!("PAN: ({:.5}; {:.5})", plan.left+plan.width/2., plan.up+plan.height/2.);
+                        } else if cycling {
+                            cycle_offset = (cycle_offset + cycle_speed).fract();
+                            let pixel_buffer = colorize(&iterations, &palette, coloring, &set_color, cycle_offset);
+                            tex.update_from_pixels(pixel_buffer.get(), pixel_buffer.width as u32, pixel_buffer.height as u32, 0, 0);
+                        }
+
+                        //only the last cursor position of the frame is computed
+                        if let Some(c) = inset_c.take() {
+                            if inset && parent.is_none() {
+                                let julia: Arc<dyn Fractal> = Arc::new(Julia::new(fractal.clone(), c));
+                                let inset_iterations = compute_iterations(&julia, &fit_to(&inset_plan, &inset_dim), &inset_dim, max_iter, DEFAULT_THREADS);
+                                inset_tex = Texture::new_from_image(&to_image(&colorize(&inset_iterations, &palette, coloring, &set_color, cycle_offset)));
+                            }
+                        }
+
+                        window.clear(&Color::black());
+                        let mut sprite = Sprite::new_with_texture(&tex).unwrap();
+                        sprite.set_position(&Vector2f::new(pan.0 as f32, pan.1 as f32));
+                        window.draw(&sprite);
+                        window.draw(&rect);
+                        if keyboard::is_key_pressed(Key::LControl) {
+                            let pixel = scale(mouse.0, mouse.1, &image_dim, &plan);
+                            let orbit = orbit(pixel, &*fractal, max_iter*(zoom_lvl+1));
+                            let mut path = VertexArray::new_init(LinesStrip, 0).unwrap();
+                            for z in &orbit.points {
+                                let x = (z.re() - plan.left)/plan.width*image_dim.width as f64;
+                                let y = (z.im() - plan.up)/plan.height*image_dim.height as f64;
+                                path.append(&Vertex::new_with_pos_color(&Vector2f::new(x as f32, y as f32), &Color::new_rgb(255, 255, 255)));
+                            }
+                            window.draw(&path);
+
+                            let label = format!("c = ({:.5}; {:.5})\niterations : {}\n{}",
+                                                pixel.re(), pixel.im(),
+                                                orbit.points.len() - 1,
+                                                match (orbit.escaped, orbit.period) {
+                                                    (true, _)          => "escaped".to_string(),
+                                                    (false, Some(p))   => format!("bounded, period {}", p),
+                                                    (false, None)      => "bounded, no period found".to_string(),
+                                                });
+                            match font {
+                                Some(ref font) => {
+                                    let mut text = Text::new_init(&label, font, 14).unwrap();
+                                    text.set_color(&Color::new_rgb(255, 255, 255));
+                                    text.set_position(&Vector2f::new(10., image_dim.height as f32 - 60.));
+                                    window.draw(&text);
+                                },
+                                //without a font the label goes to the terminal, once per change
+                                None if label != orbit_label => println!("ORBIT: {}", label.replace('\n', ", ")),
+                                None => {},
+                            }
+                            orbit_label = label;
+                        }
+                        if inset && parent.is_none() {
+                            if let Some(ref inset_tex) = inset_tex {
+                                let mut sprite = Sprite::new_with_texture(inset_tex).unwrap();
+                                sprite.set_position(&inset_frame.get_position());
+                                window.draw(&sprite);
+                                window.draw(&inset_frame);
+                            }
+                        }
+                        if let Some(ref font) = font {
+                            //the labels below the HUD start at `label_top`
+                            let mut label_top = 10.;
+                            if hud {
+                                let cursor = scale(mouse.0, mouse.1, &image_dim, &plan);
+                                let label = format!("cursor : ({:.10}; {:.10})\ncenter : ({:.10}; {:.10})\nwidth : {:.3e}\nzoom : {}\nmax_iter : {}\nrender : {:.1} ms, {} threads",
+                                                    cursor.re(), cursor.im(),
+                                                    plan.left + plan.width/2., plan.up + plan.height/2.,
+                                                    plan.width,
+                                                    magnification_label(default_plan.width/plan.width),
+                                                    max_iter*(zoom_lvl+1),
+                                                    render_time.as_secs_f64()*1000., DEFAULT_THREADS);
+                                let mut text = Text::new_init(&label, font, 14).unwrap();
+                                text.set_color(&Color::new_rgb(255, 255, 255));
+                                text.set_position(&Vector2f::new(10., 10.));
+                                let bounds = text.get_global_bounds();
+                                let mut background = RectangleShape::new_init(&Vector2f::new(bounds.width + 10., bounds.height + 10.)).unwrap();
+                                background.set_position(&Vector2f::new(bounds.left - 5., bounds.top - 5.));
+                                background.set_fill_color(&Color::new_rgba(0, 0, 0, 160));
+                                window.draw(&background);
+                                window.draw(&text);
+                                label_top = bounds.top + bounds.height + 15.;
+                            }
+                            //the name being typed, or the list of the bookmarks
+                            let label = match (&naming, listing) {
+                                (Some(name), _)        => Some(format!("bookmark name : {}_", name)),
+                                (None, Some(selected)) => Some(bookmarks.iter().enumerate()
+                                                               .map(|(index, bookmark)| format!("{} {}", if index == selected { ">" } else { " " }, bookmark.name))
+                                                               .collect::<Vec<_>>()
+                                                               .join("\n")),
+                                (None, None)           => None,
+                            };
+                            if let Some(label) = label {
+                                let mut text = Text::new_init(&label, font, 14).unwrap();
+                                text.set_color(&Color::new_rgb(255, 255, 255));
+                                text.set_position(&Vector2f::new(10., label_top));
+                                window.draw(&text);
+                            }
+                        }
+                        window.display();
+                    }
+                }
+            }
+        }
+    }
+}
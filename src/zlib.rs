@@ -0,0 +1,266 @@
+//! Minimal zlib (RFC 1950) compressor for the PNG encoder. The data is
+//! compressed in a single deflate block with the fixed Huffman codes, using
+//! a hash chain to find the LZ77 matches.
+
+const WINDOW_SIZE: usize = 32768;
+const HASH_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+                                35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+                                3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+                                  257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+                                  8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+                                  7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Writes the `count` low bits of `value`, least significant first.
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        let mut reversed = 0;
+        for bit in 0..length {
+            reversed |= ((code >> bit) & 1) << (length - 1 - bit);
+        }
+        self.write(reversed, length);
+    }
+
+    fn write_symbol(&mut self, symbol: u16) {
+        match symbol {
+            0..=143   => self.write_code(0x30 + symbol as u32, 8),
+            144..=255 => self.write_code(0x190 + (symbol - 144) as u32, 9),
+            256..=279 => self.write_code((symbol - 256) as u32, 7),
+            _         => self.write_code(0xc0 + (symbol - 280) as u32, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+fn hash(data: &[u8], position: usize) -> usize {
+    let value = (data[position] as usize) << 16 | (data[position + 1] as usize) << 8 | data[position + 2] as usize;
+    (value.wrapping_mul(2654435761) >> 8) % HASH_SIZE
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    writer.write_symbol(257 + code as u16);
+    writer.write((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+    let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    writer.write_code(code as u32, 5);
+    writer.write((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+}
+
+/// Compresses `data` into a zlib stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    writer.write(1, 1); //last block
+    writer.write(1, 2); //fixed Huffman codes
+
+    //head[h] and previous[i % WINDOW_SIZE] hold positions + 1, 0 meaning none
+    let mut head = vec![0usize; HASH_SIZE];
+    let mut previous = vec![0usize; WINDOW_SIZE];
+    let mut position = 0;
+    while position < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if position + MIN_MATCH <= data.len() {
+            let max_length = ::std::cmp::min(MAX_MATCH, data.len() - position);
+            let mut candidate = head[hash(data, position)];
+            let mut chain = 0;
+            while candidate > 0 && position - (candidate - 1) <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let start = candidate - 1;
+                let mut length = 0;
+                while length < max_length && data[start + length] == data[position + length] {
+                    length += 1;
+                }
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - start;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[start % WINDOW_SIZE];
+                chain += 1;
+            }
+        }
+
+        let step = if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            best_length
+        } else {
+            writer.write_symbol(data[position] as u16);
+            1
+        };
+        for inserted in position..position + step {
+            if inserted + MIN_MATCH <= data.len() {
+                let h = hash(data, inserted);
+                previous[inserted % WINDOW_SIZE] = head[h];
+                head[h] = inserted + 1;
+            }
+        }
+        position += step;
+    }
+    writer.write_symbol(256);
+
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(writer.finish());
+    stream.extend(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Decompresses the streams written by `compress`, which only use the fixed
+/// Huffman codes, to check them.
+#[cfg(test)]
+pub fn decompress(stream: &[u8]) -> Vec<u8> {
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.bytes[self.position/8] >> (self.position % 8)) & 1;
+            self.position += 1;
+            bit as u32
+        }
+
+        fn read(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, bit| value | self.bit() << bit)
+        }
+
+        fn read_code(&mut self, length: u32) -> u32 {
+            (0..length).fold(0, |code, _| code << 1 | self.bit())
+        }
+
+        fn read_symbol(&mut self) -> u16 {
+            let code = self.read_code(7);
+            if code < 0x18 {
+                return 256 + code as u16;
+            }
+            let code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => (code - 0x30) as u16,
+                0xc0..=0xc7 => (280 + code - 0xc0) as u16,
+                _           => (144 + (code << 1 | self.bit()) - 0x190) as u16,
+            }
+        }
+    }
+
+    assert_eq!(&stream[..2], &[0x78, 0x01]);
+    let mut reader = BitReader {bytes: &stream[2..stream.len() - 4], position: 0};
+    assert_eq!(reader.read(3), 0b11, "a single block with the fixed codes");
+    let mut data: Vec<u8> = Vec::new();
+    loop {
+        let symbol = reader.read_symbol();
+        match symbol {
+            0..=255 => data.push(symbol as u8),
+            256     => break,
+            _       => {
+                let code = (symbol - 257) as usize;
+                let length = LENGTH_BASE[code] as usize + reader.read(LENGTH_EXTRA[code] as u32) as usize;
+                let code = reader.read_code(5) as usize;
+                let distance = DISTANCE_BASE[code] as usize + reader.read(DISTANCE_EXTRA[code] as u32) as usize;
+                for _ in 0..length {
+                    let byte = data[data.len() - distance];
+                    data.push(byte);
+                }
+            },
+        }
+    }
+    let trailer = &stream[stream.len() - 4..];
+    assert_eq!(u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]), adler32(&data));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        assert_eq!(decompress(&compress(data)), data);
+    }
+
+    #[test]
+    fn adler() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn literals() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(&(0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn matches() {
+        round_trip(b"abcabcabcabcabcabc");
+        round_trip(&[7; 10000]);
+        let rows: Vec<u8> = (0..100).flat_map(|row| (0..300).map(move |x| ((x*x + row) % 7) as u8)).collect();
+        round_trip(&rows);
+        assert!(compress(&rows).len() < rows.len()/4);
+    }
+
+    #[test]
+    fn distances_up_to_the_window() {
+        //a pseudo-random block repeated after more than a window
+        let mut state = 12345u32;
+        let block: Vec<u8> = (0..WINDOW_SIZE + 1000).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+        let mut data = block.clone();
+        data.extend(&block[..5000]);
+        data.extend(&block[block.len() - 5000..]);
+        round_trip(&data);
+    }
+}